@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_spl::token_2022::spl_token_2022::onchain::invoke_transfer_checked;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 // Solana Playground Program ID
 declare_id!("11111111111111111111111111111111");
@@ -15,10 +16,23 @@ pub const INSURANCE_FEE_BPS: u16 = 100;
 pub const MAX_INSURANCE_CLAIM_PCT: u16 = 1000;
 /// Basis points divisor
 pub const BPS_DIVISOR: u64 = 10_000;
+/// Pause flag: new deposits
+pub const PAUSE_DEPOSITS: u8 = 1 << 3;
+/// Pause flag: lender withdrawals
+pub const PAUSE_WITHDRAWALS: u8 = 1 << 4;
+/// All router pause flags (bit layout shared with credit_market)
+pub const PAUSE_ALL: u8 = PAUSE_DEPOSITS | PAUSE_WITHDRAWALS;
+/// Default delay before queued admin actions can execute (48 hours)
+pub const DEFAULT_TIMELOCK_DELAY_SECS: i64 = 2 * 24 * 60 * 60;
+/// Max timelock delay (30 days)
+pub const MAX_TIMELOCK_DELAY_SECS: i64 = 30 * 24 * 60 * 60;
+/// Max signers in an admin multisig
+pub const MAX_MULTISIG_SIGNERS: usize = 10;
 
 /// LIQUIDITY ROUTER
 /// Auto-routes lender funds between Kamino (passive) and P2P loans (active)
 /// Now with diversification caps and insurance pool
+/// Works with SPL Token and Token-2022 mints (transfer hooks, transfer fees)
 #[program]
 pub mod liquidity_router {
     use super::*;
@@ -35,6 +49,11 @@ pub mod liquidity_router {
         config.kamino_pool = kamino_pool;
         config.kamino_rate_bps = 600; // 6% APY default
         config.fee_bps = 50; // 0.5% protocol fee
+        config.pending_admin = Pubkey::default();
+        config.timelock_delay_secs = DEFAULT_TIMELOCK_DELAY_SECS;
+        config.next_action_id = 1;
+        config.pause_authority = ctx.accounts.admin.key();
+        config.paused_flags = 0;
         config.bump = ctx.bumps.config;
         
         // Initialize liquidity pool
@@ -52,16 +71,33 @@ pub mod liquidity_router {
     }
 
     /// Lender deposits USDC - router decides where it goes
-    pub fn deposit(
-        ctx: Context<Deposit>,
+    pub fn deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, Deposit<'info>>,
         amount: u64,
     ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PAUSE_DEPOSITS),
+            ErrorCode::InstructionPaused
+        );
+
         // Mainnet safety: Max $100 per deposit (6 decimals = 100_000_000)
         require!(
             amount <= 100_000_000,
             ErrorCode::ExceedsMaxDeposit
         );
         
+        // Transfer USDC from lender to router vault
+        // Credit what the vault actually received (Token-2022 mints may charge a transfer fee)
+        let amount = transfer_tokens(
+            ctx.accounts.lender_usdc.to_account_info(),
+            &ctx.accounts.usdc_mint,
+            &mut ctx.accounts.router_vault,
+            ctx.accounts.lender.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[],
+        )?;
+        
         let position = &mut ctx.accounts.position;
         let pool = &mut ctx.accounts.liquidity_pool;
         let clock = Clock::get()?;
@@ -105,15 +141,6 @@ pub mod liquidity_router {
         pool.total_deposits += amount;
         position.updated_at = clock.unix_timestamp;
         
-        // Transfer USDC from lender to router vault
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.lender_usdc.to_account_info(),
-            to: ctx.accounts.router_vault.to_account_info(),
-            authority: ctx.accounts.lender.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
-        token::transfer(cpi_ctx, amount)?;
-        
         msg!("Deposit complete. Total: {} USDC, Pool: {} USDC", 
             position.total_deposited, pool.total_deposits);
         Ok(())
@@ -373,10 +400,15 @@ pub mod liquidity_router {
 
     /// Lender withdraws funds
     /// Priority: Kamino first (liquid), then P2P if possible
-    pub fn withdraw(
-        ctx: Context<Withdraw>,
+    pub fn withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>,
         amount: u64,
     ) -> Result<()> {
+        require!(
+            !ctx.accounts.config.is_paused(PAUSE_WITHDRAWALS),
+            ErrorCode::InstructionPaused
+        );
+
         let position = &mut ctx.accounts.position;
         let pool = &mut ctx.accounts.liquidity_pool;
         
@@ -412,22 +444,20 @@ pub mod liquidity_router {
         pool.total_deposits -= amount;
         
         // Transfer USDC to lender
-        let seeds = &[b"router_vault", &[ctx.bumps.router_vault]];
+        let seeds = &[b"router_vault".as_ref(), &[ctx.bumps.router_vault]];
         let signer = &[&seeds[..]];
         
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.router_vault.to_account_info(),
-            to: ctx.accounts.lender_usdc.to_account_info(),
-            authority: ctx.accounts.router_vault.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(), 
-            cpi_accounts, 
-            signer
-        );
-        token::transfer(cpi_ctx, amount)?;
+        let received = transfer_tokens(
+            ctx.accounts.router_vault.to_account_info(),
+            &ctx.accounts.usdc_mint,
+            &mut ctx.accounts.lender_usdc,
+            ctx.accounts.router_vault.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            signer,
+        )?;
         
-        msg!("Withdrawal complete: {} USDC", amount);
+        msg!("Withdrawal complete: {} USDC ({} received)", amount, received);
         Ok(())
     }
 
@@ -459,15 +489,327 @@ pub mod liquidity_router {
     }
 
     /// Admin: Update Kamino APY
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
     pub fn update_kamino_rate(
         ctx: Context<AdminUpdate>,
         new_rate_bps: u16,
     ) -> Result<()> {
         let config = &mut ctx.accounts.config;
         require!(config.admin == ctx.accounts.admin.key(), ErrorCode::Unauthorized);
+        require!(config.timelock_delay_secs == 0, ErrorCode::TimelockActive);
+        
+        apply_router_action(config, &RouterAction::UpdateKaminoRate { new_rate_bps })
+    }
+
+    /// Admin: Propose a new admin, who must call `accept_admin` to take over
+    /// Proposing `Pubkey::default()` cancels a pending handover
+    pub fn propose_admin(
+        ctx: Context<AdminUpdate>,
+        new_admin: Pubkey,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(config.admin == ctx.accounts.admin.key(), ErrorCode::Unauthorized);
         
-        config.kamino_rate_bps = new_rate_bps;
-        msg!("Kamino rate updated to {} bps", new_rate_bps);
+        config.pending_admin = new_admin;
+        msg!("Admin handover proposed: {} -> {}", config.admin, new_admin);
+        
+        emit!(AdminProposed {
+            admin: config.admin,
+            pending_admin: new_admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Proposed admin: Accept a pending admin handover
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let new_admin = ctx.accounts.new_admin.key();
+        require!(config.pending_admin == new_admin, ErrorCode::Unauthorized);
+        
+        let previous_admin = config.admin;
+        config.admin = new_admin;
+        config.pending_admin = Pubkey::default();
+        msg!("Admin transferred: {} -> {}", previous_admin, new_admin);
+        
+        emit!(AdminTransferred {
+            previous_admin,
+            new_admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Admin: Queue a rate or timelock change
+    /// Anyone can execute it once `timelock_delay_secs` has passed
+    pub fn queue_action(
+        ctx: Context<QueueAction>,
+        action: RouterAction,
+    ) -> Result<()> {
+        validate_router_action(&action)?;
+        
+        let clock = Clock::get()?;
+        let config = &mut ctx.accounts.config;
+        require!(config.admin == ctx.accounts.admin.key(), ErrorCode::Unauthorized);
+        
+        let action_id = config.next_action_id;
+        config.next_action_id = action_id.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        let eta = clock.unix_timestamp
+            .checked_add(config.timelock_delay_secs)
+            .ok_or(ErrorCode::MathOverflow)?;
+        
+        let queued = &mut ctx.accounts.queued_action;
+        queued.id = action_id;
+        queued.proposer = ctx.accounts.admin.key();
+        queued.action = action.clone();
+        queued.queued_at = clock.unix_timestamp;
+        queued.eta = eta;
+        queued.bump = ctx.bumps.queued_action;
+        
+        msg!("Queued router action #{} (executable at {})", action_id, eta);
+        
+        emit!(ActionQueued {
+            action_id,
+            action,
+            eta,
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Anyone: Execute a queued action once its timelock has elapsed
+    pub fn execute_action(ctx: Context<ExecuteAction>) -> Result<()> {
+        let clock = Clock::get()?;
+        let queued = &ctx.accounts.queued_action;
+        require!(clock.unix_timestamp >= queued.eta, ErrorCode::TimelockNotElapsed);
+        
+        apply_router_action(&mut ctx.accounts.config, &queued.action)?;
+        msg!("Executed router action #{}", queued.id);
+        
+        emit!(ActionExecuted {
+            action_id: queued.id,
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Admin: Cancel a queued action before it executes
+    /// While a multisig is admin, the proposer of an approved `MultisigAction::CancelAction`
+    /// cancels it instead, passing the proposal as `approval`
+    pub fn cancel_action(ctx: Context<CancelAction>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        authorize_admin(
+            &accounts.config,
+            &accounts.authority,
+            accounts.multisig.as_ref(),
+            accounts.approval.as_ref(),
+            &MultisigAction::CancelAction { action_id: accounts.queued_action.id },
+        )?;
+        msg!("Cancelled router action #{}", ctx.accounts.queued_action.id);
+        
+        emit!(ActionCancelled {
+            action_id: ctx.accounts.queued_action.id,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // =========================================================================
+    // MULTISIG ADMIN
+    // =========================================================================
+    // The router admin can be an M-of-N signer set. Hand over with
+    // `propose_admin(multisig)` then an approved `MultisigAction::AcceptAdmin`.
+
+    /// Create an M-of-N signer set that can act as the router admin
+    pub fn create_multisig(
+        ctx: Context<CreateMultisig>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        validate_multisig_signers(&signers, threshold)?;
+        
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.creator = ctx.accounts.creator.key();
+        multisig.signers = signers;
+        multisig.threshold = threshold;
+        multisig.owner_set_seqno = 0;
+        multisig.next_proposal_id = 1;
+        multisig.bump = ctx.bumps.multisig;
+        
+        msg!("Multisig created: {}-of-{}", threshold, multisig.signers.len());
+        Ok(())
+    }
+
+    /// Multisig signer: Propose an admin action (counts as the first approval)
+    pub fn propose_multisig_action(
+        ctx: Context<ProposeMultisigAction>,
+        action: MultisigAction,
+    ) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        let proposer = ctx.accounts.proposer.key();
+        require!(multisig.signers.contains(&proposer), ErrorCode::NotMultisigSigner);
+        
+        match &action {
+            MultisigAction::Router(router_action) => validate_router_action(router_action)?,
+            MultisigAction::ChangeSigners { signers, threshold } => {
+                validate_multisig_signers(signers, *threshold)?
+            }
+            MultisigAction::ProposeAdmin { .. }
+            | MultisigAction::AcceptAdmin
+            | MultisigAction::CancelAction { .. } => {}
+        }
+        
+        let clock = Clock::get()?;
+        let proposal_id = multisig.next_proposal_id;
+        multisig.next_proposal_id = proposal_id.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.multisig = multisig.key();
+        proposal.id = proposal_id;
+        proposal.proposer = proposer;
+        proposal.action = action;
+        proposal.approvals = vec![proposer];
+        proposal.owner_set_seqno = multisig.owner_set_seqno;
+        proposal.eta = 0;
+        proposal.bump = ctx.bumps.proposal;
+        
+        if proposal.approvals.len() >= multisig.threshold as usize {
+            proposal.eta = clock.unix_timestamp
+                .checked_add(multisig_action_delay(&ctx.accounts.config, &proposal.action))
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        
+        msg!("Multisig proposal #{} created by {}", proposal_id, proposer);
+        Ok(())
+    }
+
+    /// Multisig signer: Approve a proposal
+    /// At threshold it becomes executable (after the timelock for rate changes)
+    pub fn approve_multisig_action(ctx: Context<ApproveMultisigAction>) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let proposal = &mut ctx.accounts.proposal;
+        let approver = ctx.accounts.approver.key();
+        
+        require!(multisig.signers.contains(&approver), ErrorCode::NotMultisigSigner);
+        require!(proposal.owner_set_seqno == multisig.owner_set_seqno, ErrorCode::StaleProposal);
+        require!(!proposal.approvals.contains(&approver), ErrorCode::AlreadyApproved);
+        
+        proposal.approvals.push(approver);
+        
+        if proposal.eta == 0 && proposal.approvals.len() >= multisig.threshold as usize {
+            proposal.eta = Clock::get()?.unix_timestamp
+                .checked_add(multisig_action_delay(&ctx.accounts.config, &proposal.action))
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        
+        msg!("Multisig proposal #{}: {}/{} approvals", 
+            proposal.id, proposal.approvals.len(), multisig.threshold);
+        Ok(())
+    }
+
+    /// Anyone: Execute an approved multisig proposal
+    pub fn execute_multisig_action(ctx: Context<ExecuteMultisigAction>) -> Result<()> {
+        let clock = Clock::get()?;
+        let proposal = &ctx.accounts.proposal;
+        let multisig_key = ctx.accounts.multisig.key();
+        
+        require!(
+            proposal.owner_set_seqno == ctx.accounts.multisig.owner_set_seqno,
+            ErrorCode::StaleProposal
+        );
+        require!(proposal.eta != 0, ErrorCode::ThresholdNotMet);
+        require!(clock.unix_timestamp >= proposal.eta, ErrorCode::TimelockNotElapsed);
+        
+        let config = &mut ctx.accounts.config;
+        match &proposal.action {
+            MultisigAction::Router(router_action) => {
+                require!(config.admin == multisig_key, ErrorCode::Unauthorized);
+                apply_router_action(config, router_action)?;
+            }
+            MultisigAction::ProposeAdmin { new_admin } => {
+                require!(config.admin == multisig_key, ErrorCode::Unauthorized);
+                config.pending_admin = *new_admin;
+                
+                emit!(AdminProposed {
+                    admin: config.admin,
+                    pending_admin: *new_admin,
+                    timestamp: clock.unix_timestamp,
+                });
+            }
+            MultisigAction::AcceptAdmin => {
+                require!(config.pending_admin == multisig_key, ErrorCode::Unauthorized);
+                let previous_admin = config.admin;
+                config.admin = multisig_key;
+                config.pending_admin = Pubkey::default();
+                
+                emit!(AdminTransferred {
+                    previous_admin,
+                    new_admin: multisig_key,
+                    timestamp: clock.unix_timestamp,
+                });
+            }
+            MultisigAction::ChangeSigners { signers, threshold } => {
+                let multisig = &mut ctx.accounts.multisig;
+                multisig.signers = signers.clone();
+                multisig.threshold = *threshold;
+                // Invalidates approvals on every other open proposal
+                multisig.owner_set_seqno = multisig.owner_set_seqno
+                    .checked_add(1)
+                    .ok_or(ErrorCode::MathOverflow)?;
+            }
+            MultisigAction::CancelAction { .. } => {
+                return err!(ErrorCode::ApprovalOnlyAction);
+            }
+        }
+        
+        msg!("Executed multisig proposal #{}", proposal.id);
+        Ok(())
+    }
+
+    /// Proposer: Cancel a multisig proposal before it executes
+    pub fn cancel_multisig_action(ctx: Context<CancelMultisigAction>) -> Result<()> {
+        let proposal = &ctx.accounts.proposal;
+        require!(proposal.proposer == ctx.accounts.proposer.key(), ErrorCode::Unauthorized);
+        
+        msg!("Cancelled multisig proposal #{}", proposal.id);
+        Ok(())
+    }
+
+    /// Admin: Set the account allowed to pause and unpause the router
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
+    pub fn set_pause_authority(
+        ctx: Context<AdminUpdate>,
+        pause_authority: Pubkey,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(config.admin == ctx.accounts.admin.key(), ErrorCode::Unauthorized);
+        require!(config.timelock_delay_secs == 0, ErrorCode::TimelockActive);
+        
+        apply_router_action(config, &RouterAction::SetPauseAuthority { pause_authority })
+    }
+
+    /// Pause authority or admin: Pause/unpause deposits and withdrawals
+    /// `paused_flags` replaces the current bitflags, see `PAUSE_*`
+    pub fn set_paused(
+        ctx: Context<PauseUpdate>,
+        paused_flags: u8,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let authority = ctx.accounts.authority.key();
+        require!(
+            config.pause_authority == authority || config.admin == authority,
+            ErrorCode::Unauthorized
+        );
+        require!(paused_flags & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+        
+        config.paused_flags = paused_flags;
+        msg!("Router pause flags set to {:#010b}", paused_flags);
+        
+        emit!(PauseFlagsUpdated {
+            authority,
+            paused_flags,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
         Ok(())
     }
 
@@ -697,6 +1039,125 @@ pub mod liquidity_router {
     }
 }
 
+// === HELPERS ===
+
+/// Transfer with `transfer_checked`, forwarding transfer hook accounts from `remaining_accounts`
+/// Returns what `to` actually received (less than `amount` if the mint charges a transfer fee)
+fn transfer_tokens<'info>(
+    from: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &mut InterfaceAccount<'info, TokenAccount>,
+    authority: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    let token_program_id = *mint_info.owner;
+    let balance_before = to.amount;
+    
+    invoke_transfer_checked(
+        &token_program_id,
+        from,
+        mint_info,
+        to.to_account_info(),
+        authority,
+        remaining_accounts,
+        amount,
+        mint.decimals,
+        signer_seeds,
+    )?;
+    
+    to.reload()?;
+    Ok(to.amount.checked_sub(balance_before).ok_or(ErrorCode::MathOverflow)?)
+}
+
+/// Check a router action's parameters before it is queued
+fn validate_router_action(action: &RouterAction) -> Result<()> {
+    match action {
+        RouterAction::UpdateKaminoRate { .. } | RouterAction::SetPauseAuthority { .. } => {}
+        RouterAction::SetTimelockDelay { delay_secs } => {
+            require!(
+                *delay_secs >= 0 && *delay_secs <= MAX_TIMELOCK_DELAY_SECS,
+                ErrorCode::InvalidTimelockDelay
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Check a multisig signer set: non-empty, no duplicates, 1 <= threshold <= signers
+fn validate_multisig_signers(signers: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(
+        !signers.is_empty() && signers.len() <= MAX_MULTISIG_SIGNERS,
+        ErrorCode::InvalidMultisig
+    );
+    require!(
+        threshold > 0 && threshold as usize <= signers.len(),
+        ErrorCode::InvalidMultisig
+    );
+    for (i, signer) in signers.iter().enumerate() {
+        require!(!signers[i + 1..].contains(signer), ErrorCode::InvalidMultisig);
+    }
+    Ok(())
+}
+
+/// Rate changes approved by a multisig still wait out the timelock
+fn multisig_action_delay(config: &RouterConfig, action: &MultisigAction) -> i64 {
+    match action {
+        MultisigAction::Router(_) => config.timelock_delay_secs,
+        _ => 0,
+    }
+}
+
+/// Apply a router action to the config
+fn apply_router_action(config: &mut RouterConfig, action: &RouterAction) -> Result<()> {
+    match action {
+        RouterAction::UpdateKaminoRate { new_rate_bps } => {
+            config.kamino_rate_bps = *new_rate_bps;
+            msg!("Kamino rate updated to {} bps", new_rate_bps);
+        }
+        RouterAction::SetTimelockDelay { delay_secs } => {
+            config.timelock_delay_secs = *delay_secs;
+            msg!("Timelock delay updated to {} secs", delay_secs);
+        }
+        RouterAction::SetPauseAuthority { pause_authority } => {
+            config.pause_authority = *pause_authority;
+            msg!("Pause authority updated to {}", pause_authority);
+        }
+    }
+    Ok(())
+}
+
+/// Let `authority` act as admin: either it is the admin, or the admin is `multisig` and
+/// `approval` is its executable proposal for `action`, made by `authority`.
+/// The approval is closed back to the proposer so it can only be used once.
+fn authorize_admin<'info>(
+    config: &RouterConfig,
+    authority: &Signer<'info>,
+    multisig: Option<&Account<'info, Multisig>>,
+    approval: Option<&Account<'info, MultisigProposal>>,
+    action: &MultisigAction,
+) -> Result<()> {
+    if config.admin == authority.key() {
+        return Ok(());
+    }
+    
+    let (multisig, approval) = multisig.zip(approval).ok_or(ErrorCode::Unauthorized)?;
+    require!(config.admin == multisig.key(), ErrorCode::Unauthorized);
+    require!(
+        approval.multisig == multisig.key() && approval.proposer == authority.key(),
+        ErrorCode::Unauthorized
+    );
+    require!(approval.owner_set_seqno == multisig.owner_set_seqno, ErrorCode::StaleProposal);
+    require!(approval.eta != 0, ErrorCode::ThresholdNotMet);
+    require!(Clock::get()?.unix_timestamp >= approval.eta, ErrorCode::TimelockNotElapsed);
+    require!(approval.action == *action, ErrorCode::ApprovalActionMismatch);
+    
+    msg!("Executed multisig proposal #{}", approval.id);
+    approval.close(authority.to_account_info())
+}
+
 // === ACCOUNT STRUCTS ===
 
 #[derive(Accounts)]
@@ -742,8 +1203,8 @@ pub struct Deposit<'info> {
     #[account(mut, seeds = [b"liquidity_pool"], bump)]
     pub liquidity_pool: Account<'info, LiquidityPool>,
     
-    #[account(mut)]
-    pub lender_usdc: Account<'info, TokenAccount>,
+    #[account(mut, constraint = lender_usdc.mint == usdc_mint.key() @ ErrorCode::MintMismatch)]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
     
     #[account(
         init_if_needed,
@@ -753,13 +1214,13 @@ pub struct Deposit<'info> {
         seeds = [b"router_vault"],
         bump
     )]
-    pub router_vault: Account<'info, TokenAccount>,
+    pub router_vault: InterfaceAccount<'info, TokenAccount>,
     
-    pub usdc_mint: Account<'info, token::Mint>,
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
     
     pub config: Account<'info, RouterConfig>,
     
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -855,13 +1316,23 @@ pub struct Withdraw<'info> {
     #[account(mut, seeds = [b"liquidity_pool"], bump)]
     pub liquidity_pool: Account<'info, LiquidityPool>,
     
-    #[account(mut)]
-    pub lender_usdc: Account<'info, TokenAccount>,
+    #[account(mut, constraint = lender_usdc.mint == usdc_mint.key() @ ErrorCode::MintMismatch)]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"router_vault"],
+        bump,
+        constraint = router_vault.mint == usdc_mint.key() @ ErrorCode::MintMismatch
+    )]
+    pub router_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
     
-    #[account(mut, seeds = [b"router_vault"], bump)]
-    pub router_vault: Account<'info, TokenAccount>,
+    #[account(seeds = [b"router_config"], bump = config.bump)]
+    pub config: Account<'info, RouterConfig>,
     
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
@@ -882,6 +1353,177 @@ pub struct AdminUpdate<'info> {
     pub config: Account<'info, RouterConfig>,
 }
 
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub new_admin: Signer<'info>,
+    
+    #[account(mut, seeds = [b"router_config"], bump = config.bump)]
+    pub config: Account<'info, RouterConfig>,
+}
+
+#[derive(Accounts)]
+pub struct QueueAction<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(mut, seeds = [b"router_config"], bump = config.bump)]
+    pub config: Account<'info, RouterConfig>,
+    
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + QueuedAction::SIZE,
+        seeds = [b"queued_action", config.next_action_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteAction<'info> {
+    /// Anyone can execute once the timelock has elapsed
+    pub executor: Signer<'info>,
+    
+    #[account(mut, seeds = [b"router_config"], bump = config.bump)]
+    pub config: Account<'info, RouterConfig>,
+    
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"queued_action", queued_action.id.to_le_bytes().as_ref()],
+        bump = queued_action.bump
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+    
+    /// CHECK: Receives the queued action's rent, must match its proposer
+    #[account(mut, address = queued_action.proposer)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAction<'info> {
+    /// The admin, or the proposer of an approved multisig proposal for this call
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(seeds = [b"router_config"], bump = config.bump)]
+    pub config: Account<'info, RouterConfig>,
+    
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"queued_action", queued_action.id.to_le_bytes().as_ref()],
+        bump = queued_action.bump
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+    
+    /// CHECK: Receives the queued action's rent, must match its proposer
+    #[account(mut, address = queued_action.proposer)]
+    pub proposer: UncheckedAccount<'info>,
+    
+    /// Required with `approval` when a multisig is admin
+    pub multisig: Option<Account<'info, Multisig>>,
+    
+    #[account(mut)]
+    pub approval: Option<Account<'info, MultisigProposal>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMultisig<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + Multisig::SIZE,
+        seeds = [b"multisig", creator.key().as_ref()],
+        bump
+    )]
+    pub multisig: Account<'info, Multisig>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeMultisigAction<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    
+    #[account(mut, seeds = [b"multisig", multisig.creator.as_ref()], bump = multisig.bump)]
+    pub multisig: Account<'info, Multisig>,
+    
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + MultisigProposal::SIZE,
+        seeds = [
+            b"multisig_proposal",
+            multisig.key().as_ref(),
+            multisig.next_proposal_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+    
+    #[account(seeds = [b"router_config"], bump = config.bump)]
+    pub config: Account<'info, RouterConfig>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveMultisigAction<'info> {
+    pub approver: Signer<'info>,
+    
+    #[account(seeds = [b"multisig", multisig.creator.as_ref()], bump = multisig.bump)]
+    pub multisig: Account<'info, Multisig>,
+    
+    #[account(mut, has_one = multisig)]
+    pub proposal: Account<'info, MultisigProposal>,
+    
+    #[account(seeds = [b"router_config"], bump = config.bump)]
+    pub config: Account<'info, RouterConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteMultisigAction<'info> {
+    /// Anyone can execute an approved proposal
+    pub executor: Signer<'info>,
+    
+    #[account(mut, seeds = [b"multisig", multisig.creator.as_ref()], bump = multisig.bump)]
+    pub multisig: Account<'info, Multisig>,
+    
+    #[account(mut, has_one = multisig, has_one = proposer, close = proposer)]
+    pub proposal: Account<'info, MultisigProposal>,
+    
+    /// CHECK: Receives the proposal's rent, must match its proposer
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+    
+    #[account(mut, seeds = [b"router_config"], bump = config.bump)]
+    pub config: Account<'info, RouterConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CancelMultisigAction<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    
+    #[account(mut, close = proposer)]
+    pub proposal: Account<'info, MultisigProposal>,
+}
+
+#[derive(Accounts)]
+pub struct PauseUpdate<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(mut, seeds = [b"router_config"], bump = config.bump)]
+    pub config: Account<'info, RouterConfig>,
+}
+
 #[derive(Accounts)]
 pub struct GetPoolStats<'info> {
     #[account(seeds = [b"liquidity_pool"], bump)]
@@ -951,11 +1593,74 @@ pub struct RouterConfig {
     pub kamino_pool: Pubkey,
     pub kamino_rate_bps: u16,
     pub fee_bps: u16,
+    /// Can pause/unpause alongside the admin
+    pub pause_authority: Pubkey,
+    /// PAUSE_* bitflags
+    pub paused_flags: u8,
+    /// Proposed admin awaiting `accept_admin`
+    pub pending_admin: Pubkey,
+    /// Delay before queued admin actions can execute
+    pub timelock_delay_secs: i64,
+    pub next_action_id: u64,
     pub bump: u8,
 }
 
 impl RouterConfig {
-    pub const SIZE: usize = 32 + 32 + 32 + 2 + 2 + 1;
+    pub const SIZE: usize = 32 + 32 + 32 + 2 + 2 + 32 + 1 + 32 + 8 + 8 + 1;
+
+    pub fn is_paused(&self, flag: u8) -> bool {
+        self.paused_flags & flag != 0
+    }
+}
+
+/// Admin action waiting out the timelock
+#[account]
+pub struct QueuedAction {
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub action: RouterAction,
+    pub queued_at: i64,
+    /// Earliest execution time
+    pub eta: i64,
+    pub bump: u8,
+}
+
+impl QueuedAction {
+    pub const SIZE: usize = 8 + 32 + RouterAction::SIZE + 8 + 8 + 1;
+}
+
+/// M-of-N signer set that can hold the router admin role
+#[account]
+pub struct Multisig {
+    pub creator: Pubkey,
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    /// Bumped when signers change, invalidating open proposals
+    pub owner_set_seqno: u32,
+    pub next_proposal_id: u64,
+    pub bump: u8,
+}
+
+impl Multisig {
+    pub const SIZE: usize = 32 + 4 + (32 * MAX_MULTISIG_SIGNERS) + 1 + 4 + 8 + 1;
+}
+
+#[account]
+pub struct MultisigProposal {
+    pub multisig: Pubkey,
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub action: MultisigAction,
+    pub approvals: Vec<Pubkey>,
+    pub owner_set_seqno: u32,
+    /// 0 until the threshold is met
+    pub eta: i64,
+    pub bump: u8,
+}
+
+impl MultisigProposal {
+    pub const SIZE: usize = 32 + 8 + 32 + MultisigAction::SIZE 
+        + 4 + (32 * MAX_MULTISIG_SIGNERS) + 4 + 8 + 1;
 }
 
 /// Global liquidity pool tracking
@@ -1047,6 +1752,77 @@ pub struct AllocationSnapshot {
     pub insurance_balance: u64,
 }
 
+#[event]
+pub struct PauseFlagsUpdated {
+    pub authority: Pubkey,
+    pub paused_flags: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ActionQueued {
+    pub action_id: u64,
+    pub action: RouterAction,
+    pub eta: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ActionExecuted {
+    pub action_id: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ActionCancelled {
+    pub action_id: u64,
+    pub timestamp: i64,
+}
+
+/// Admin changes that must pass through the timelock
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum RouterAction {
+    UpdateKaminoRate { new_rate_bps: u16 },
+    SetTimelockDelay { delay_secs: i64 },
+    SetPauseAuthority { pause_authority: Pubkey },
+}
+
+impl RouterAction {
+    /// Enum tag + largest variant (SetPauseAuthority)
+    pub const SIZE: usize = 1 + 32;
+}
+
+/// Actions a multisig can take as router admin
+/// `CancelAction` approves a `cancel_action` call by the proposer rather than being run
+/// by `execute_multisig_action`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum MultisigAction {
+    Router(RouterAction),
+    ProposeAdmin { new_admin: Pubkey },
+    AcceptAdmin,
+    ChangeSigners { signers: Vec<Pubkey>, threshold: u8 },
+    CancelAction { action_id: u64 },
+}
+
+impl MultisigAction {
+    /// Enum tag + largest variant (ChangeSigners)
+    pub const SIZE: usize = 1 + 4 + (32 * MAX_MULTISIG_SIGNERS) + 1;
+}
+
 /// Route destination enum for events
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
 pub enum RouteDestination {
@@ -1072,4 +1848,30 @@ pub enum ErrorCode {
     InsufficientInsurance,
     #[msg("Exceeds diversification cap")]
     ExceedsDiversificationCap,
+    #[msg("Instruction is paused")]
+    InstructionPaused,
+    #[msg("Invalid pause flags")]
+    InvalidPauseFlags,
+    #[msg("Timelock is active - queue this change with queue_action")]
+    TimelockActive,
+    #[msg("Timelock has not elapsed for this action")]
+    TimelockNotElapsed,
+    #[msg("Invalid timelock delay")]
+    InvalidTimelockDelay,
+    #[msg("Invalid multisig signers or threshold")]
+    InvalidMultisig,
+    #[msg("Signer is not part of the multisig")]
+    NotMultisigSigner,
+    #[msg("Signer has already approved this proposal")]
+    AlreadyApproved,
+    #[msg("Proposal has not reached the approval threshold")]
+    ThresholdNotMet,
+    #[msg("Proposal was created under a previous signer set")]
+    StaleProposal,
+    #[msg("Token account mint does not match the vault mint")]
+    MintMismatch,
+    #[msg("Proposal approves an admin instruction, call it with the proposal instead")]
+    ApprovalOnlyAction,
+    #[msg("Approved proposal is for a different action")]
+    ApprovalActionMismatch,
 }
@@ -1,5 +1,24 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::{
+    hash::hash,
+    instruction::{
+        get_stack_height, AccountMeta, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT,
+    },
+    program::{invoke, invoke_signed},
+    sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+};
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        transfer_fee::TransferFeeConfig, transfer_hook, BaseStateWithExtensions, ExtensionType,
+        StateWithExtensions,
+    },
+    onchain::invoke_transfer_checked,
+};
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 declare_id!("CRDTmk5GYLqSh8fPGvdMgdmAHxYhAuP5YzJfDL8W9Xyz");
 
@@ -11,17 +30,137 @@ declare_id!("CRDTmk5GYLqSh8fPGvdMgdmAHxYhAuP5YzJfDL8W9Xyz");
 /// - 89% → Lender
 /// - 10% → Insurance Pool
 /// - 1%  → Protocol Treasury
+///
+/// The insurance share is a dynamic premium: it starts from `insurance_fee_bps`,
+/// scales with the pool's solvency ratio and is discounted by borrower credit tier.
+///
+/// Markets are keyed by mint: each `GlobalState` lives at `[b"global_state", mint]`
+/// with its own fees, insurance pool and whitelist. Offers, loans and vaults are
+/// scoped to their market's mint and every token account must match it.
+///
+/// Mints may belong to SPL Token or Token-2022. All transfers use `transfer_checked`
+/// and forward `remaining_accounts` to the mint's transfer hook, if it has one.
+/// Balances are recorded from what vaults actually receive, and payments owed to
+/// lenders, the insurance pool and the treasury are grossed up for transfer fees.
+///
+/// Each market can issue a Token-2022 credit token, minted 1:1 against USDC held in its
+/// credit reserve. The credit mint carries the `transfer_hook` program, so disbursed loan
+/// funds can only move to approved venues, and names the market's `CreditWrapper` as
+/// permanent delegate so liquidation can claw credit back from the borrower.
+///
+/// Loans can also hold other tokens, in token accounts owned by the loan and registered
+/// as positions. Health is the USDC value of the vault, the credit the borrower still
+/// holds and those positions, priced from Pyth-style feeds configured per market.
+///
+/// Offers can accept collateral up to an LTV. Collateral is held in a loan-owned vault,
+/// lets the borrower exceed their reputation tier limit by its LTV-adjusted value, is
+/// released on repayment and goes to the lender on liquidation or default.
+///
+/// Positions still held at liquidation are sold by Dutch auction: the price starts above
+/// the oracle price and decays over time, bidders pay USDC to the lender until the debt
+/// is covered and to the borrower after that.
+///
+/// Once a loan's health drops below its warning threshold anyone can issue a margin call.
+/// The borrower then has a cure window to top up the vault, and an unhealthy loan that is
+/// not past due can only be liquidated after the window ends.
+///
+/// Offers are fixed-rate by default. Variable-rate loans accrue through a market-wide
+/// borrow index whose rate follows a kinked curve over the market's utilization: lent
+/// principal against that plus the liquidity still escrowed in active offers. Fixed-rate
+/// offers can also compound per second, rounded in the lender's favor.
+///
+/// Lenders can charge a minimum interest period, a flat origination fee and a penalty for
+/// repaying before `end_time`. `repay_loan` splits these like interest.
+///
+/// Lenders can also lend their idle offer escrow as flash loans. `flash_borrow` checks
+/// through the instructions sysvar that a `flash_repay` for the same offer follows it in
+/// the transaction, which returns the funds plus a fee for the lender and insurance pool.
 
 // Fee constants in basis points (100 bps = 1%)
 pub const INSURANCE_FEE_BPS: u64 = 1000;  // 10% of interest
 pub const PROTOCOL_FEE_BPS: u64 = 100;    // 1% of interest
 pub const LENDER_SHARE_BPS: u64 = 8900;   // 89% of interest
 
+// Insurance premium curve defaults
+pub const DEFAULT_MIN_PREMIUM_BPS: u16 = 500;        // 5% of interest floor
+pub const DEFAULT_MAX_PREMIUM_BPS: u16 = 2000;       // 20% of interest ceiling
+pub const DEFAULT_TARGET_SOLVENCY_BPS: u32 = 20000;  // Pool holds 2x what it has paid out
+pub const DEFAULT_TIER_DISCOUNT_BPS: u16 = 500;      // 5% off the premium per tier above 1
+pub const MAX_SOLVENCY_RATIO_BPS: u32 = 100_000;     // Ratio is capped at 10x
+
+// Reputation tier limits are in whole tokens at USDC's 6 decimals
+pub const TIER_LIMIT_DECIMALS: u8 = 6;
+
+// Pause flags (bitflags on GlobalState.paused_flags)
+// Repayment and lender exits (cancel offer, claim insurance) are never pausable.
+pub const PAUSE_NEW_OFFERS: u8 = 1 << 0;
+pub const PAUSE_NEW_LOANS: u8 = 1 << 1;
+pub const PAUSE_EXECUTE_TRADE: u8 = 1 << 2;
+pub const PAUSE_ALL: u8 = PAUSE_NEW_OFFERS | PAUSE_NEW_LOANS | PAUSE_EXECUTE_TRADE;
+
+// Default circuit breaker, which keeps the times of the last max_defaults + 1 defaults
+pub const MAX_BREAKER_DEFAULTS: usize = 16;
+pub const BREAKER_HISTORY_LEN: usize = MAX_BREAKER_DEFAULTS + 1;
+
+// Governance timelock for fee and whitelist changes
+pub const DEFAULT_TIMELOCK_DELAY_SECS: i64 = 2 * 24 * 60 * 60; // 48 hours
+pub const MAX_TIMELOCK_DELAY_SECS: i64 = 30 * 24 * 60 * 60;    // 30 days
+
+pub const MAX_WHITELISTED_PROGRAMS: usize = 20;
+pub const MAX_MULTISIG_SIGNERS: usize = 10;
+
+// Multi-asset valuation
+pub const MAX_PRICE_FEEDS: usize = 8;
+pub const MAX_LOAN_POSITIONS: usize = 4;
+pub const MAX_COLLATERAL_MINTS: usize = 4;
+
+// Offer repayment terms
+pub const MAX_ORIGINATION_FEE_BPS: u16 = 500;               // 5% of principal
+pub const MAX_PREPAYMENT_PENALTY_BPS: u16 = 500;            // 5% of principal
+
+// Flash loans
+pub const MAX_FLASH_FEE_BPS: u16 = 100;                     // 1% of the amount borrowed
+
+// Margin calls
+pub const DEFAULT_WARNING_BUFFER_BPS: u16 = 1000;           // Warning threshold above liquidation
+pub const MAX_WARNING_THRESHOLD_BPS: u16 = 9500;             // Below a fresh loan's ~10000 health
+pub const MARGIN_CALL_CURE_WINDOW_SECS: i64 = 24 * 60 * 60; // 24 hours
+
+// Liquidation auctions, priced as a fraction of the oracle price
+pub const AUCTION_START_PRICE_BPS: u64 = 11_000;            // 10% premium
+pub const AUCTION_FLOOR_PRICE_BPS: u64 = 7_000;             // 30% discount
+pub const AUCTION_DURATION_SECS: i64 = 2 * 60 * 60;         // Decay from start to floor
+
+// Variable-rate model defaults (annual rates in bps)
+pub const DEFAULT_BASE_RATE_BPS: u16 = 200;                 // 2% at zero utilization
+pub const DEFAULT_RATE_SLOPE1_BPS: u16 = 400;               // +4% up to optimal utilization
+pub const DEFAULT_RATE_SLOPE2_BPS: u16 = 7500;              // +75% from optimal to full
+pub const DEFAULT_OPTIMAL_UTILIZATION_BPS: u16 = 8000;      // 80%
+pub const MAX_VARIABLE_RATE_BPS: u32 = 50_000;              // 500% at full utilization
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+pub const BORROW_INDEX_ONE: u128 = WAD;
+
+// Per-loan spend policies
+pub const MAX_POLICY_PROGRAMS: usize = 8;
+pub const TRADE_DAY_SECS: i64 = 24 * 60 * 60;
+
+// Session key permissions, as bitflags on `SessionKey::allowed_actions`
+pub const SESSION_EXECUTE_TRADE: u8 = 1 << 0;
+pub const SESSION_PAY_MERCHANT: u8 = 1 << 1;
+pub const SESSION_REPAY: u8 = 1 << 2;
+pub const SESSION_ALL: u8 = SESSION_EXECUTE_TRADE | SESSION_PAY_MERCHANT | SESSION_REPAY;
+pub const MAX_SESSION_DURATION_SECS: i64 = 30 * 24 * 60 * 60; // 30 days
+
+// Merchant payments
+pub const MAX_LOAN_MERCHANTS: usize = 8;
+pub const MAX_MERCHANT_LABEL_LEN: usize = 32;
+pub const MAX_PAYMENT_MEMO_LEN: usize = 64;
+
 #[program]
 pub mod credit_market {
     use super::*;
 
-    /// Initialize the global state for the credit market
+    /// Initialize the market for a mint (USDC, USDT, PYUSD, wrapped SOL, ...)
     pub fn initialize(
         ctx: Context<Initialize>,
         insurance_pool: Pubkey,
@@ -29,6 +168,7 @@ pub mod credit_market {
     ) -> Result<()> {
         let global_state = &mut ctx.accounts.global_state;
         global_state.admin = ctx.accounts.admin.key();
+        global_state.mint = ctx.accounts.mint.key();
         global_state.next_loan_id = 1;
         global_state.fee_bps = PROTOCOL_FEE_BPS as u16; // 1% protocol fee
         global_state.insurance_fee_bps = INSURANCE_FEE_BPS as u16; // 10% insurance fee
@@ -36,11 +176,36 @@ pub mod credit_market {
         global_state.treasury = treasury;
         global_state.total_insurance_collected = 0;
         global_state.total_insurance_claimed = 0;
+        global_state.solvency_ratio_bps = MAX_SOLVENCY_RATIO_BPS;
+        global_state.min_premium_bps = DEFAULT_MIN_PREMIUM_BPS;
+        global_state.max_premium_bps = DEFAULT_MAX_PREMIUM_BPS;
+        global_state.target_solvency_bps = DEFAULT_TARGET_SOLVENCY_BPS;
+        global_state.tier_discount_bps = DEFAULT_TIER_DISCOUNT_BPS;
+        global_state.pending_admin = Pubkey::default();
+        global_state.timelock_delay_secs = DEFAULT_TIMELOCK_DELAY_SECS;
+        global_state.next_action_id = 1;
+        global_state.pause_authority = ctx.accounts.admin.key();
+        global_state.paused_flags = 0;
+        global_state.breaker_window_secs = 0; // Circuit breaker disabled until configured
+        global_state.breaker_max_defaults = 0;
+        global_state.breaker_recent_defaults = vec![];
         global_state.whitelisted_programs = vec![];
+        global_state.price_feeds = vec![];
+        global_state.rate_model = RateModel {
+            base_rate_bps: DEFAULT_BASE_RATE_BPS,
+            slope1_bps: DEFAULT_RATE_SLOPE1_BPS,
+            slope2_bps: DEFAULT_RATE_SLOPE2_BPS,
+            optimal_utilization_bps: DEFAULT_OPTIMAL_UTILIZATION_BPS,
+        };
+        global_state.borrow_index = BORROW_INDEX_ONE;
+        global_state.index_updated_at = Clock::get()?.unix_timestamp;
+        global_state.total_offered = 0;
+        global_state.total_borrowed = 0;
         global_state.bump = ctx.bumps.global_state;
 
         emit!(MarketInitialized {
             admin: global_state.admin,
+            mint: global_state.mint,
             insurance_pool,
             treasury,
             timestamp: Clock::get()?.unix_timestamp,
@@ -50,51 +215,81 @@ pub mod credit_market {
     }
 
     /// Post a lending offer with configurable terms
-    pub fn post_lend_offer(
-        ctx: Context<PostLendOffer>,
+    /// `liquidation_threshold_bps` is capped at `MAX_WARNING_THRESHOLD_BPS`, so the warning
+    /// threshold above it stays below the health a fresh loan opens at.
+    pub fn post_lend_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, PostLendOffer<'info>>,
         amount: u64,
         min_rate_bps: u16,
         max_duration_secs: u64,
         min_reputation: u16,
         liquidation_threshold_bps: u16,
+        spend_policy: SpendPolicy,
     ) -> Result<()> {
+        require!(
+            !ctx.accounts.global_state.is_paused(PAUSE_NEW_OFFERS),
+            CreditMarketError::InstructionPaused
+        );
         require!(amount > 0, CreditMarketError::InvalidAmount);
         require!(min_rate_bps <= 10000, CreditMarketError::InvalidRate);
         require!(max_duration_secs > 0, CreditMarketError::InvalidDuration);
         require!(
-            liquidation_threshold_bps >= 5000 && liquidation_threshold_bps <= 10000,
+            liquidation_threshold_bps >= 5000
+                && liquidation_threshold_bps <= MAX_WARNING_THRESHOLD_BPS,
             CreditMarketError::InvalidLiquidationThreshold
         );
+        validate_spend_policy(&spend_policy)?;
+
+        // Transfer tokens to escrow vault, the offer is sized by what actually arrived
+        let received = transfer_tokens(
+            ctx.accounts.lender_usdc.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.escrow_vault,
+            ctx.accounts.lender.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[],
+        )?;
+        require!(received > 0, CreditMarketError::InvalidAmount);
 
         let offer = &mut ctx.accounts.offer;
         offer.lender = ctx.accounts.lender.key();
-        offer.amount = amount;
+        offer.mint = ctx.accounts.mint.key();
+        offer.amount = received;
         offer.min_rate_bps = min_rate_bps;
         offer.max_duration_secs = max_duration_secs;
         offer.min_reputation = min_reputation;
         offer.liquidation_threshold_bps = liquidation_threshold_bps;
+        offer.warning_threshold_bps = liquidation_threshold_bps
+            .saturating_add(DEFAULT_WARNING_BUFFER_BPS)
+            .min(MAX_WARNING_THRESHOLD_BPS);
+        offer.spend_policy = spend_policy;
+        offer.collateral_mints = Vec::new();
+        offer.ltv_bps = 0;
+        offer.rate_mode = RateMode::Fixed;
+        offer.min_interest_secs = 0;
+        offer.origination_fee_bps = 0;
+        offer.prepayment_penalty_bps = 0;
+        offer.flash_loans_enabled = false;
+        offer.flash_fee_bps = 0;
+        offer.flash_loan_amount = 0;
         offer.is_active = true;
         offer.created_at = Clock::get()?.unix_timestamp;
         offer.bump = ctx.bumps.offer;
 
-        // Transfer USDC to escrow vault
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.lender_usdc.to_account_info(),
-            to: ctx.accounts.escrow_vault.to_account_info(),
-            authority: ctx.accounts.lender.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, amount)?;
-
         // Increment global offer counter
         let global_state = &mut ctx.accounts.global_state;
+        accrue_borrow_index(global_state, Clock::get()?.unix_timestamp)?;
+        global_state.total_offered = global_state.total_offered
+            .checked_add(received)
+            .ok_or(CreditMarketError::MathOverflow)?;
         global_state.next_loan_id = global_state.next_loan_id.checked_add(1)
             .ok_or(CreditMarketError::MathOverflow)?;
 
         emit!(LendOfferPosted {
             lender: offer.lender,
-            amount,
+            mint: offer.mint,
+            amount: offer.amount,
             min_rate_bps,
             max_duration_secs,
             liquidation_threshold_bps,
@@ -105,9 +300,12 @@ pub mod credit_market {
     }
 
     /// Cancel an active lending offer and return funds
-    pub fn cancel_lend_offer(ctx: Context<CancelLendOffer>) -> Result<()> {
+    pub fn cancel_lend_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelLendOffer<'info>>,
+    ) -> Result<()> {
         let offer = &mut ctx.accounts.offer;
         require!(offer.is_active, CreditMarketError::OfferNotActive);
+        require!(offer.flash_loan_amount == 0, CreditMarketError::FlashLoanActive);
         require!(
             offer.lender == ctx.accounts.lender.key(),
             CreditMarketError::Unauthorized
@@ -115,22 +313,28 @@ pub mod credit_market {
 
         offer.is_active = false;
 
+        let global_state = &mut ctx.accounts.global_state;
+        accrue_borrow_index(global_state, Clock::get()?.unix_timestamp)?;
+        global_state.total_offered = global_state.total_offered.saturating_sub(offer.amount);
+
         // Return funds from escrow to lender
         let seeds = &[
             b"escrow",
+            offer.mint.as_ref(),
             offer.lender.as_ref(),
             &[ctx.bumps.escrow_vault],
         ];
         let signer_seeds = &[&seeds[..]];
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.escrow_vault.to_account_info(),
-            to: ctx.accounts.lender_usdc.to_account_info(),
-            authority: ctx.accounts.escrow_vault.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-        token::transfer(cpi_ctx, offer.amount)?;
+        transfer_tokens(
+            ctx.accounts.escrow_vault.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.lender_usdc,
+            ctx.accounts.escrow_vault.to_account_info(),
+            ctx.remaining_accounts,
+            offer.amount,
+            signer_seeds,
+        )?;
 
         emit!(LendOfferCancelled {
             lender: offer.lender,
@@ -153,6 +357,7 @@ pub mod credit_market {
 
         let request = &mut ctx.accounts.request;
         request.borrower = ctx.accounts.borrower.key();
+        request.mint = ctx.accounts.global_state.mint;
         request.amount = amount;
         request.max_rate_bps = max_rate_bps;
         request.duration_secs = duration_secs;
@@ -172,9 +377,35 @@ pub mod credit_market {
     }
 
     /// Accept a lending offer (borrower accepts lender's terms)
-    pub fn accept_lend_offer(ctx: Context<AcceptLendOffer>) -> Result<()> {
+    /// The offer amount must fit the borrower's credit tier limit in whole tokens of the
+    /// mint, plus the LTV-adjusted value of `collateral_amount` if the offer accepts collateral.
+    /// `rate_mode`, the `max_*` repayment terms and `max_warning_threshold_bps` are what the
+    /// borrower expects, so the lender can't switch or raise them first.
+    pub fn accept_lend_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, AcceptLendOffer<'info>>,
+        collateral_amount: u64,
+        rate_mode: RateMode,
+        max_min_interest_secs: u64,
+        max_origination_fee_bps: u16,
+        max_prepayment_penalty_bps: u16,
+        max_warning_threshold_bps: u16,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.global_state.is_paused(PAUSE_NEW_LOANS),
+            CreditMarketError::InstructionPaused
+        );
+
         let offer = &mut ctx.accounts.offer;
         require!(offer.is_active, CreditMarketError::OfferNotActive);
+        require!(offer.flash_loan_amount == 0, CreditMarketError::FlashLoanActive);
+        require!(offer.rate_mode == rate_mode, CreditMarketError::OfferTermsChanged);
+        require!(
+            offer.min_interest_secs <= max_min_interest_secs
+                && offer.origination_fee_bps <= max_origination_fee_bps
+                && offer.prepayment_penalty_bps <= max_prepayment_penalty_bps
+                && offer.warning_threshold_bps <= max_warning_threshold_bps,
+            CreditMarketError::OfferTermsChanged
+        );
 
         // Check borrower reputation meets minimum
         let borrower_profile = &ctx.accounts.borrower_profile;
@@ -182,21 +413,101 @@ pub mod credit_market {
             borrower_profile.reputation_score >= offer.min_reputation,
             CreditMarketError::InsufficientReputation
         );
+        let (_, tier_limit) = reputation::calculate_credit_tier(
+            borrower_profile.loans_repaid,
+            borrower_profile.defaults,
+        );
+        let tier_limit = scale_tier_limit(tier_limit, ctx.accounts.mint.decimals)?;
 
         offer.is_active = false;
 
         let clock = Clock::get()?;
+
+        // Collateral raises the borrowing limit by its LTV-adjusted oracle value
+        let mut collateral_received = 0;
+        let mut borrowing_power: u64 = 0;
+        if collateral_amount > 0 {
+            let collateral_mint = ctx.accounts.collateral_mint
+                .as_ref()
+                .ok_or(CreditMarketError::CollateralAccountsMissing)?;
+            require!(
+                offer.ltv_bps > 0 && offer.collateral_mints.contains(&collateral_mint.key()),
+                CreditMarketError::CollateralNotAccepted
+            );
+            let borrower_collateral = ctx.accounts.borrower_collateral
+                .as_ref()
+                .ok_or(CreditMarketError::CollateralAccountsMissing)?;
+            let collateral_vault = ctx.accounts.collateral_vault
+                .as_mut()
+                .ok_or(CreditMarketError::CollateralAccountsMissing)?;
+            let collateral_price_feed = ctx.accounts.collateral_price_feed
+                .as_ref()
+                .ok_or(CreditMarketError::CollateralAccountsMissing)?;
+
+            collateral_received = transfer_tokens(
+                borrower_collateral.to_account_info(),
+                collateral_mint,
+                collateral_vault,
+                ctx.accounts.borrower.to_account_info(),
+                ctx.remaining_accounts,
+                collateral_amount,
+                &[],
+            )?;
+            let collateral_value = oracle_value(
+                &ctx.accounts.global_state.price_feeds,
+                &collateral_mint.key(),
+                collateral_price_feed,
+                collateral_received,
+                collateral_mint.decimals,
+                ctx.accounts.mint.decimals,
+                clock.unix_timestamp,
+            )?;
+            borrowing_power = ((collateral_value as u128)
+                .checked_mul(offer.ltv_bps as u128)
+                .ok_or(CreditMarketError::MathOverflow)?
+                / 10000) as u64;
+        }
+        require!(
+            offer.amount
+                <= tier_limit
+                    .checked_add(borrowing_power)
+                    .ok_or(CreditMarketError::MathOverflow)?,
+            CreditMarketError::CreditLimitExceeded
+        );
         let global_state = &mut ctx.accounts.global_state;
         let loan_id = global_state.next_loan_id;
         global_state.next_loan_id = loan_id.checked_add(1)
             .ok_or(CreditMarketError::MathOverflow)?;
+        accrue_borrow_index(global_state, clock.unix_timestamp)?;
+
+        // Transfer funds from escrow to loan vault (borrower can use via execute_trade)
+        // Principal is what the loan vault actually received
+        let escrow_seeds = &[
+            b"escrow",
+            offer.mint.as_ref(),
+            offer.lender.as_ref(),
+            &[ctx.bumps.escrow_vault],
+        ];
+        let signer_seeds = &[&escrow_seeds[..]];
+
+        let received = transfer_tokens(
+            ctx.accounts.escrow_vault.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.loan_vault,
+            ctx.accounts.escrow_vault.to_account_info(),
+            ctx.remaining_accounts,
+            offer.amount,
+            signer_seeds,
+        )?;
+        require!(received > 0, CreditMarketError::InvalidAmount);
 
         // Initialize loan
         let loan = &mut ctx.accounts.loan;
         loan.id = loan_id;
         loan.lender = offer.lender;
         loan.borrower = ctx.accounts.borrower.key();
-        loan.principal = offer.amount;
+        loan.mint = offer.mint;
+        loan.principal = received;
         loan.rate_bps = offer.min_rate_bps;
         loan.start_time = clock.unix_timestamp;
         loan.end_time = clock.unix_timestamp
@@ -205,34 +516,65 @@ pub mod credit_market {
         loan.status = LoanStatus::Active;
         loan.vault = ctx.accounts.loan_vault.key();
         loan.liquidation_threshold_bps = offer.liquidation_threshold_bps;
+        loan.warning_threshold_bps = offer.warning_threshold_bps;
+        loan.margin_call_deadline = 0;
         loan.insurance_claimed = false;
+        loan.insurance_payout = 0;
+        loan.recovered_amount = 0;
+        loan.credit_outstanding = 0;
+        loan.credit_account = Pubkey::default();
+        loan.total_spent = 0;
+        loan.allowed_merchants = Vec::new();
+        loan.spend_policy = offer.spend_policy.clone();
+        loan.trade_day_start = 0;
+        loan.trades_today = 0;
+        loan.positions = Vec::new();
+        loan.collateral_mint = Pubkey::default();
+        loan.collateral_vault = Pubkey::default();
+        loan.collateral_amount = collateral_received;
+        loan.auction_start = 0;
+        loan.auction_debt_remaining = 0;
+        loan.rate_mode = offer.rate_mode;
+        loan.entry_borrow_index = ctx.accounts.global_state.borrow_index;
+        loan.exit_borrow_index = 0;
+        loan.min_interest_secs = offer.min_interest_secs;
+        loan.origination_fee_bps = offer.origination_fee_bps;
+        loan.prepayment_penalty_bps = offer.prepayment_penalty_bps;
+        // The collateral vault is valued like any other position
+        if collateral_received > 0 {
+            if let (Some(collateral_mint), Some(collateral_vault)) =
+                (&ctx.accounts.collateral_mint, &ctx.accounts.collateral_vault)
+            {
+                loan.collateral_mint = collateral_mint.key();
+                loan.collateral_vault = collateral_vault.key();
+                loan.positions.push(LoanPosition {
+                    token_account: collateral_vault.key(),
+                    mint: collateral_mint.key(),
+                    decimals: collateral_mint.decimals,
+                });
+            }
+        }
         loan.bump = ctx.bumps.loan;
 
-        // Transfer funds from escrow to loan vault (borrower can use via execute_trade)
-        let escrow_seeds = &[
-            b"escrow",
-            offer.lender.as_ref(),
-            &[ctx.bumps.escrow_vault],
-        ];
-        let signer_seeds = &[&escrow_seeds[..]];
-
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.escrow_vault.to_account_info(),
-            to: ctx.accounts.loan_vault.to_account_info(),
-            authority: ctx.accounts.escrow_vault.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-        token::transfer(cpi_ctx, offer.amount)?;
+        // The escrowed offer is now lent out
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.total_offered = global_state.total_offered.saturating_sub(offer.amount);
+        global_state.total_borrowed = global_state.total_borrowed
+            .checked_add(received)
+            .ok_or(CreditMarketError::MathOverflow)?;
 
         emit!(LoanCreated {
             loan_id,
             lender: loan.lender,
             borrower: loan.borrower,
+            mint: loan.mint,
             principal: loan.principal,
             rate_bps: loan.rate_bps,
+            rate_mode: loan.rate_mode,
             end_time: loan.end_time,
             liquidation_threshold_bps: loan.liquidation_threshold_bps,
+            collateral_mint: loan.collateral_mint,
+            collateral_amount: loan.collateral_amount,
             timestamp: clock.unix_timestamp,
         });
 
@@ -240,84 +582,110 @@ pub mod credit_market {
     }
 
     /// Repay a loan in full
-    /// Interest is split: 89% to lender, 10% to insurance pool, 1% to protocol treasury
-    pub fn repay_loan(ctx: Context<RepayLoan>) -> Result<()> {
+    /// Interest is split between lender, insurance pool (dynamic premium) and 1% to protocol treasury
+    /// A session key can repay if it is a delegate on `borrower_usdc`.
+    /// Fails if the total charged, including any transfer fees, exceeds `max_total`.
+    pub fn repay_loan<'info>(
+        ctx: Context<'_, '_, '_, 'info, RepayLoan<'info>>,
+        max_total: u64,
+    ) -> Result<()> {
+        authorize_borrower(
+            &ctx.accounts.loan,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.session_key.as_mut(),
+            SESSION_REPAY,
+            0,
+        )?;
+
         let loan = &mut ctx.accounts.loan;
-        require!(loan.status == LoanStatus::Active, CreditMarketError::LoanNotActive);
-        require!(
-            loan.borrower == ctx.accounts.borrower.key(),
-            CreditMarketError::Unauthorized
-        );
+        require!(loan.is_outstanding(), CreditMarketError::LoanNotActive);
+
+        // Disbursed credit has to be unwrapped back into the vault first
+        require!(loan.credit_outstanding == 0, CreditMarketError::CreditOutstanding);
 
         let clock = Clock::get()?;
         let global_state = &mut ctx.accounts.global_state;
-        
-        // Calculate total repayment with interest
-        let duration_secs = clock.unix_timestamp
-            .checked_sub(loan.start_time)
-            .ok_or(CreditMarketError::MathOverflow)? as u64;
-        let interest = calculate_interest(loan.principal, loan.rate_bps, duration_secs)?;
-        
-        // Split interest: 89% lender, 10% insurance, 1% protocol
-        let insurance_fee = interest
-            .checked_mul(INSURANCE_FEE_BPS)
-            .ok_or(CreditMarketError::MathOverflow)?
-            .checked_div(10000)
-            .ok_or(CreditMarketError::MathOverflow)?;
-        let protocol_fee = interest
-            .checked_mul(PROTOCOL_FEE_BPS)
-            .ok_or(CreditMarketError::MathOverflow)?
-            .checked_div(10000)
-            .ok_or(CreditMarketError::MathOverflow)?;
-        let lender_interest = interest
-            .checked_sub(insurance_fee)
-            .ok_or(CreditMarketError::MathOverflow)?
-            .checked_sub(protocol_fee)
-            .ok_or(CreditMarketError::MathOverflow)?;
-        
+
+        let RepaymentQuote {
+            interest,
+            origination_fee,
+            prepayment_penalty,
+            lender_interest,
+            insurance_fee,
+            insurance_premium_bps,
+            protocol_fee,
+            total,
+            total_charged,
+            ..
+        } = calculate_repayment(
+            loan,
+            global_state,
+            &ctx.accounts.borrower_profile,
+            &ctx.accounts.mint,
+            clock.unix_timestamp,
+        )?;
+        require!(total_charged <= max_total, CreditMarketError::RepaymentExceedsMax);
+
         // Amount to lender: principal + lender's share of interest
         let lender_amount = loan.principal
             .checked_add(lender_interest)
             .ok_or(CreditMarketError::MathOverflow)?;
 
         // Transfer principal + lender interest to lender
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.borrower_usdc.to_account_info(),
-            to: ctx.accounts.lender_usdc.to_account_info(),
-            authority: ctx.accounts.borrower.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, lender_amount)?;
+        transfer_tokens_exact(
+            ctx.accounts.borrower_usdc.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.lender_usdc,
+            ctx.accounts.authority.to_account_info(),
+            ctx.remaining_accounts,
+            lender_amount,
+        )?;
 
         // Transfer insurance fee to insurance pool
         if insurance_fee > 0 {
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.borrower_usdc.to_account_info(),
-                to: ctx.accounts.insurance_pool.to_account_info(),
-                authority: ctx.accounts.borrower.to_account_info(),
-            };
-            let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
-            token::transfer(cpi_ctx, insurance_fee)?;
+            transfer_tokens_exact(
+                ctx.accounts.borrower_usdc.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.insurance_pool,
+                ctx.accounts.authority.to_account_info(),
+                ctx.remaining_accounts,
+                insurance_fee,
+            )?;
             
             // Track total insurance collected
             global_state.total_insurance_collected = global_state.total_insurance_collected
                 .checked_add(insurance_fee)
                 .ok_or(CreditMarketError::MathOverflow)?;
+            global_state.solvency_ratio_bps = calculate_solvency_ratio_bps(
+                global_state.total_insurance_collected,
+                global_state.total_insurance_claimed,
+            )?;
         }
 
         // Transfer protocol fee to treasury
         if protocol_fee > 0 {
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.borrower_usdc.to_account_info(),
-                to: ctx.accounts.treasury.to_account_info(),
-                authority: ctx.accounts.borrower.to_account_info(),
-            };
-            let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
-            token::transfer(cpi_ctx, protocol_fee)?;
+            transfer_tokens_exact(
+                ctx.accounts.borrower_usdc.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.treasury,
+                ctx.accounts.authority.to_account_info(),
+                ctx.remaining_accounts,
+                protocol_fee,
+            )?;
         }
 
         loan.status = LoanStatus::Repaid;
+        close_loan_borrow(loan, global_state, clock.unix_timestamp)?;
+
+        release_collateral(
+            loan,
+            ctx.accounts.collateral_vault.as_ref(),
+            ctx.accounts.collateral_mint.as_ref(),
+            ctx.accounts.borrower_collateral.as_mut(),
+            ctx.remaining_accounts,
+            false,
+        )?;
+        let loan = &ctx.accounts.loan;
 
         // Update borrower reputation (+50 for successful repayment)
         let borrower_profile = &mut ctx.accounts.borrower_profile;
@@ -334,45 +702,101 @@ pub mod credit_market {
             lender: loan.lender,
             principal: loan.principal,
             interest,
+            origination_fee,
+            prepayment_penalty,
             lender_interest,
             insurance_fee,
+            insurance_premium_bps,
             protocol_fee,
-            total_repaid: loan.principal.checked_add(interest).ok_or(CreditMarketError::MathOverflow)?,
+            total_repaid: total,
             timestamp: clock.unix_timestamp,
         });
 
         Ok(())
     }
 
+    /// What `repay_loan` would charge at `at_timestamp` and how it is split (view function,
+    /// simulate to read the return data)
+    pub fn quote_repayment(ctx: Context<QuoteRepayment>, at_timestamp: i64) -> Result<RepaymentQuote> {
+        let loan = &ctx.accounts.loan;
+        require!(loan.is_outstanding(), CreditMarketError::LoanNotActive);
+        require!(at_timestamp >= loan.start_time, CreditMarketError::InvalidTimestamp);
+
+        calculate_repayment(
+            loan,
+            &ctx.accounts.global_state,
+            &ctx.accounts.borrower_profile,
+            &ctx.accounts.mint,
+            at_timestamp,
+        )
+    }
+
     /// Execute a trade using borrowed funds (whitelisted programs only)
+    /// The notional checked against the loan's spend policy and the session key's spend cap
+    /// is what the trade takes out of the loan vault, measured around the CPI.
     pub fn execute_trade(
         ctx: Context<ExecuteTrade>,
         target_program: Pubkey,
         instruction_data: Vec<u8>,
     ) -> Result<()> {
-        let loan = &ctx.accounts.loan;
-        require!(loan.status == LoanStatus::Active, CreditMarketError::LoanNotActive);
+        authorize_borrower(
+            &ctx.accounts.loan,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.session_key.as_mut(),
+            SESSION_EXECUTE_TRADE,
+            0,
+        )?;
         require!(
-            loan.borrower == ctx.accounts.borrower.key(),
-            CreditMarketError::Unauthorized
+            ctx.accounts.loan.status == LoanStatus::Active,
+            CreditMarketError::LoanNotActive
         );
 
         // Check if target program is whitelisted
         let config = &ctx.accounts.config;
+        require!(
+            !config.is_paused(PAUSE_EXECUTE_TRADE),
+            CreditMarketError::InstructionPaused
+        );
         require!(
             config.whitelisted_programs.contains(&target_program),
             CreditMarketError::ProgramNotWhitelisted
         );
 
+        let vault_before = ctx.accounts.loan_vault.amount;
+
         // Execute CPI to whitelisted program
         // Note: Actual CPI implementation would go here
         // This is a simplified version for the MVP
 
+        ctx.accounts.loan_vault.reload()?;
+        let notional = vault_before.saturating_sub(ctx.accounts.loan_vault.amount);
+
+        // Charge the session key for what actually left the vault
+        authorize_borrower(
+            &ctx.accounts.loan,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.session_key.as_mut(),
+            SESSION_EXECUTE_TRADE,
+            notional,
+        )?;
+
+        let clock = Clock::get()?;
+        let loan = &mut ctx.accounts.loan;
+        enforce_spend_policy(
+            loan,
+            vault_before,
+            &target_program,
+            notional,
+            clock.unix_timestamp,
+        )?;
+
         emit!(TradeExecuted {
             loan_id: loan.id,
             borrower: loan.borrower,
             target_program,
-            timestamp: Clock::get()?.unix_timestamp,
+            notional,
+            trades_today: loan.trades_today,
+            timestamp: clock.unix_timestamp,
         });
 
         Ok(())
@@ -380,35 +804,44 @@ pub mod credit_market {
 
     /// Liquidate an unhealthy or past-due loan
     /// Can be called by anyone (keeper bots) when conditions are met
-    pub fn liquidate_loan(ctx: Context<LiquidateLoan>) -> Result<()> {
-        let loan = &mut ctx.accounts.loan;
-        require!(loan.status == LoanStatus::Active, CreditMarketError::LoanNotActive);
+    /// `remaining_accounts` starts with a (token account, price feed) pair per loan position,
+    /// followed by any accounts the mint's transfer hook needs.
+    /// Positions left after the sweep go to a Dutch auction, see `bid_liquidation_auction`.
+    pub fn liquidate_loan<'info>(
+        ctx: Context<'_, '_, '_, 'info, LiquidateLoan<'info>>,
+    ) -> Result<()> {
+        let loan = &ctx.accounts.loan;
+        require!(loan.is_outstanding(), CreditMarketError::LoanNotActive);
 
         let clock = Clock::get()?;
         
         // Check if loan is liquidatable
         let is_past_due = clock.unix_timestamp > loan.end_time;
-        
-        // Calculate health factor based on remaining vault balance vs. expected repayment
-        let vault_balance = ctx.accounts.loan_vault.amount;
-        let duration_secs = clock.unix_timestamp
-            .checked_sub(loan.start_time)
-            .ok_or(CreditMarketError::MathOverflow)? as u64;
-        let interest = calculate_interest(loan.principal, loan.rate_bps, duration_secs)?;
-        let expected_repayment = loan.principal
-            .checked_add(interest)
-            .ok_or(CreditMarketError::MathOverflow)?;
-        
-        // Health factor = (vault_balance * 10000) / expected_repayment
-        let health_factor_bps = if expected_repayment > 0 {
-            (vault_balance as u128)
-                .checked_mul(10000)
-                .ok_or(CreditMarketError::MathOverflow)?
-                .checked_div(expected_repayment as u128)
-                .ok_or(CreditMarketError::MathOverflow)? as u16
-        } else {
-            10000
-        };
+
+        let (position_accounts, hook_accounts) =
+            split_position_accounts(loan, ctx.remaining_accounts)?;
+        let LoanHealth {
+            vault_balance,
+            credit_held,
+            position_values,
+            positions_value,
+            expected_repayment,
+            health_factor_bps,
+            ..
+        } = loan_health(
+            loan,
+            &ctx.accounts.loan_vault,
+            ctx.accounts.borrower_credit.as_ref(),
+            &ctx.accounts.global_state,
+            position_accounts,
+            ctx.accounts.mint.decimals,
+            clock.unix_timestamp,
+        )?;
+        // Seized collateral counts towards the debt, other positions are auctioned
+        let collateral_value = loan.positions
+            .iter()
+            .position(|p| loan.collateral_amount > 0 && p.token_account == loan.collateral_vault)
+            .map_or(0, |i| position_values[i]);
 
         let is_unhealthy = health_factor_bps < loan.liquidation_threshold_bps;
 
@@ -416,8 +849,16 @@ pub mod credit_market {
             is_past_due || is_unhealthy,
             CreditMarketError::LoanNotLiquidatable
         );
+        // Unhealthy loans get a margin call and its cure window first
+        let cure_window_over = loan.status == LoanStatus::MarginCall
+            && clock.unix_timestamp >= loan.margin_call_deadline;
+        require!(
+            is_past_due || cure_window_over,
+            CreditMarketError::MarginCallPending
+        );
 
         // Store values we need for seeds before borrowing
+        let loan_mint = loan.mint;
         let loan_id_bytes = loan.id.to_le_bytes();
         let loan_bump = loan.bump;
 
@@ -425,24 +866,112 @@ pub mod credit_market {
         if vault_balance > 0 {
             let loan_seeds = &[
                 b"loan",
+                loan_mint.as_ref(),
                 loan_id_bytes.as_ref(),
                 &[loan_bump],
             ];
             let signer_seeds = &[&loan_seeds[..]];
 
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.loan_vault.to_account_info(),
-                to: ctx.accounts.lender_usdc.to_account_info(),
-                authority: ctx.accounts.loan.to_account_info(),
-            };
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-            token::transfer(cpi_ctx, vault_balance)?;
+            transfer_tokens(
+                ctx.accounts.loan_vault.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.lender_usdc,
+                ctx.accounts.loan.to_account_info(),
+                hook_accounts,
+                vault_balance,
+                signer_seeds,
+            )?;
+        }
+
+        // Burn the borrower's credit as permanent delegate and release its USDC to the lender
+        if credit_held > 0 {
+            let accounts = &mut *ctx.accounts;
+            let credit_wrapper = accounts
+                .credit_wrapper
+                .as_mut()
+                .ok_or(CreditMarketError::CreditAccountsMissing)?;
+            let credit_mint = accounts
+                .credit_mint
+                .as_ref()
+                .ok_or(CreditMarketError::CreditAccountsMissing)?;
+            let credit_reserve = accounts
+                .credit_reserve
+                .as_ref()
+                .ok_or(CreditMarketError::CreditAccountsMissing)?;
+            let borrower_credit = accounts
+                .borrower_credit
+                .as_ref()
+                .ok_or(CreditMarketError::CreditAccountsMissing)?;
+            require!(
+                credit_wrapper.market == accounts.global_state.key(),
+                CreditMarketError::MarketMismatch
+            );
+            require!(
+                credit_mint.key() == credit_wrapper.credit_mint,
+                CreditMarketError::MintMismatch
+            );
+            require!(
+                credit_reserve.key() == credit_wrapper.reserve,
+                CreditMarketError::InvalidCreditReserve
+            );
+
+            let market = credit_wrapper.market;
+            let wrapper_seeds = &[b"credit_wrapper", market.as_ref(), &[credit_wrapper.bump]];
+            burn_credit(
+                credit_mint,
+                borrower_credit.to_account_info(),
+                credit_wrapper.to_account_info(),
+                credit_held,
+                &[&wrapper_seeds[..]],
+            )?;
+            release_credit_reserve(
+                credit_wrapper,
+                &accounts.mint,
+                credit_reserve.to_account_info(),
+                &mut accounts.lender_usdc,
+                hook_accounts,
+                credit_held,
+            )?;
+            credit_wrapper.total_wrapped = credit_wrapper.total_wrapped
+                .checked_sub(credit_held)
+                .ok_or(CreditMarketError::MathOverflow)?;
         }
 
+        release_collateral(
+            &mut ctx.accounts.loan,
+            ctx.accounts.collateral_vault.as_ref(),
+            ctx.accounts.collateral_mint.as_ref(),
+            ctx.accounts.lender_collateral.as_mut(),
+            hook_accounts,
+            true,
+        )?;
+
         // Now update loan status after the transfer
         let loan = &mut ctx.accounts.loan;
         loan.status = LoanStatus::Liquidated;
+        close_loan_borrow(loan, &mut ctx.accounts.global_state, clock.unix_timestamp)?;
+        // Credit no longer held by the borrower has been spent and cannot be recovered
+        loan.credit_outstanding = 0;
+
+        let recovered = vault_balance
+            .checked_add(credit_held)
+            .and_then(|v| v.checked_add(collateral_value))
+            .ok_or(CreditMarketError::MathOverflow)?;
+        loan.recovered_amount = loan.recovered_amount
+            .checked_add(recovered)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        if !loan.positions.is_empty() {
+            loan.auction_start = clock.unix_timestamp;
+            loan.auction_debt_remaining = expected_repayment.saturating_sub(recovered);
+
+            emit!(LiquidationAuctionStarted {
+                loan_id: loan.id,
+                debt_remaining: loan.auction_debt_remaining,
+                positions: loan.positions.len() as u8,
+                timestamp: clock.unix_timestamp,
+            });
+        }
 
         // Penalize borrower reputation (-200 points, +1 default)
         let borrower_profile = &mut ctx.accounts.borrower_profile;
@@ -452,12 +981,16 @@ pub mod credit_market {
             .checked_add(1)
             .ok_or(CreditMarketError::MathOverflow)?;
 
+        record_default_for_breaker(&mut ctx.accounts.global_state, clock.unix_timestamp)?;
+
         emit!(LoanLiquidated {
             loan_id: loan.id,
             borrower: loan.borrower,
             lender: loan.lender,
             liquidator: ctx.accounts.liquidator.key(),
             vault_balance_recovered: vault_balance,
+            credit_clawed_back: credit_held,
+            positions_value,
             principal: loan.principal,
             health_factor_bps,
             is_past_due,
@@ -467,10 +1000,272 @@ pub mod credit_market {
         Ok(())
     }
 
+    /// Put a loan whose health is below its warning threshold into `MarginCall` and start
+    /// the cure window. Permissionless; `remaining_accounts` holds a (token account, price
+    /// feed) pair per loan position.
+    pub fn issue_margin_call<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdateMarginCall<'info>>,
+    ) -> Result<()> {
+        let loan = &ctx.accounts.loan;
+        require!(loan.status == LoanStatus::Active, CreditMarketError::LoanNotActive);
+
+        let clock = Clock::get()?;
+        let (position_accounts, _) = split_position_accounts(loan, ctx.remaining_accounts)?;
+        let health = loan_health(
+            loan,
+            &ctx.accounts.loan_vault,
+            ctx.accounts.borrower_credit.as_ref(),
+            &ctx.accounts.global_state,
+            position_accounts,
+            ctx.accounts.mint.decimals,
+            clock.unix_timestamp,
+        )?;
+        require!(
+            health.health_factor_bps < loan.warning_threshold_bps,
+            CreditMarketError::LoanNotAtRisk
+        );
+
+        let loan = &mut ctx.accounts.loan;
+        loan.status = LoanStatus::MarginCall;
+        loan.margin_call_deadline = clock.unix_timestamp
+            .checked_add(MARGIN_CALL_CURE_WINDOW_SECS)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        emit!(MarginCallIssued {
+            loan_id: loan.id,
+            borrower: loan.borrower,
+            caller: ctx.accounts.caller.key(),
+            health_factor_bps: health.health_factor_bps,
+            warning_threshold_bps: loan.warning_threshold_bps,
+            deadline: loan.margin_call_deadline,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Return a loan in `MarginCall` to `Active` once its health is back at or above the
+    /// warning threshold. Permissionless, same accounts as `issue_margin_call`.
+    pub fn clear_margin_call<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdateMarginCall<'info>>,
+    ) -> Result<()> {
+        let loan = &ctx.accounts.loan;
+        require!(loan.status == LoanStatus::MarginCall, CreditMarketError::NoMarginCall);
+
+        let clock = Clock::get()?;
+        let (position_accounts, _) = split_position_accounts(loan, ctx.remaining_accounts)?;
+        let health = loan_health(
+            loan,
+            &ctx.accounts.loan_vault,
+            ctx.accounts.borrower_credit.as_ref(),
+            &ctx.accounts.global_state,
+            position_accounts,
+            ctx.accounts.mint.decimals,
+            clock.unix_timestamp,
+        )?;
+        require!(
+            health.health_factor_bps >= loan.warning_threshold_bps,
+            CreditMarketError::LoanStillAtRisk
+        );
+
+        let loan = &mut ctx.accounts.loan;
+        loan.status = LoanStatus::Active;
+        loan.margin_call_deadline = 0;
+
+        emit!(MarginCallCleared {
+            loan_id: loan.id,
+            health_factor_bps: health.health_factor_bps,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Add funds to a loan's vault, e.g. to cure a margin call (borrower only)
+    pub fn top_up_loan<'info>(
+        ctx: Context<'_, '_, '_, 'info, TopUpLoan<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, CreditMarketError::InvalidAmount);
+
+        let received = transfer_tokens(
+            ctx.accounts.borrower_usdc.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.loan_vault,
+            ctx.accounts.borrower.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[],
+        )?;
+
+        emit!(LoanToppedUp {
+            loan_id: ctx.accounts.loan.id,
+            amount: received,
+            vault_balance: ctx.accounts.loan_vault.amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Loan health as `liquidate_loan` sees it (view function, simulate to read the return
+    /// data). `remaining_accounts` holds a (token account, price feed) pair per loan position.
+    pub fn get_loan_health<'info>(
+        ctx: Context<'_, '_, '_, 'info, GetLoanHealth<'info>>,
+    ) -> Result<LoanHealthInfo> {
+        let loan = &ctx.accounts.loan;
+        require!(loan.is_outstanding(), CreditMarketError::LoanNotActive);
+
+        let now = Clock::get()?.unix_timestamp;
+        let (position_accounts, _) = split_position_accounts(loan, ctx.remaining_accounts)?;
+        let health = loan_health(
+            loan,
+            &ctx.accounts.loan_vault,
+            ctx.accounts.borrower_credit.as_ref(),
+            &ctx.accounts.global_state,
+            position_accounts,
+            ctx.accounts.mint.decimals,
+            now,
+        )?;
+
+        Ok(LoanHealthInfo {
+            principal: loan.principal,
+            accrued_interest: health.expected_repayment - loan.principal,
+            amount_due: health.expected_repayment,
+            vault_balance: health.vault_balance,
+            credit_held: health.credit_held,
+            positions_value: health.positions_value,
+            portfolio_value: health.portfolio_value,
+            health_factor_bps: health.health_factor_bps,
+            warning_threshold_bps: loan.warning_threshold_bps,
+            liquidation_threshold_bps: loan.liquidation_threshold_bps,
+            is_past_due: now > loan.end_time,
+            seconds_until_end: loan.end_time.saturating_sub(now),
+            margin_call_deadline: loan.margin_call_deadline,
+            is_liquidatable: loan.is_liquidatable(health.health_factor_bps, now),
+        })
+    }
+
+    /// Buy `amount` of a liquidated loan's position at the current auction price.
+    /// Proceeds go to the lender until the remaining debt is covered, then to the borrower.
+    /// `max_payment` bounds the USDC the bidder pays.
+    pub fn bid_liquidation_auction<'info>(
+        ctx: Context<'_, '_, '_, 'info, BidLiquidationAuction<'info>>,
+        amount: u64,
+        max_payment: u64,
+    ) -> Result<()> {
+        let loan = &ctx.accounts.loan;
+        let position_account_key = ctx.accounts.position_account.key();
+        let position = loan.positions
+            .iter()
+            .find(|p| p.token_account == position_account_key)
+            .cloned()
+            .ok_or(CreditMarketError::InvalidPosition)?;
+        require!(
+            ctx.accounts.asset_mint.key() == position.mint,
+            CreditMarketError::MintMismatch
+        );
+        require!(
+            amount > 0 && amount <= ctx.accounts.position_account.amount,
+            CreditMarketError::InvalidAmount
+        );
+
+        let clock = Clock::get()?;
+        let value = oracle_value(
+            &ctx.accounts.global_state.price_feeds,
+            &position.mint,
+            &ctx.accounts.price_feed,
+            amount,
+            position.decimals,
+            ctx.accounts.mint.decimals,
+            clock.unix_timestamp,
+        )?;
+        let price_bps = auction_price_bps(loan.auction_start, clock.unix_timestamp)?;
+        let payment = ((value as u128)
+            .checked_mul(price_bps as u128)
+            .ok_or(CreditMarketError::MathOverflow)?
+            / 10000) as u64;
+        require!(payment > 0, CreditMarketError::InvalidAmount);
+        require!(payment <= max_payment, CreditMarketError::AuctionSlippageExceeded);
+
+        let to_lender = payment.min(loan.auction_debt_remaining);
+        let to_borrower = payment - to_lender;
+
+        // Bidder covers any transfer fee on the proceeds
+        if to_lender > 0 {
+            transfer_tokens_exact(
+                ctx.accounts.bidder_usdc.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.lender_usdc,
+                ctx.accounts.bidder.to_account_info(),
+                ctx.remaining_accounts,
+                to_lender,
+            )?;
+        }
+        if to_borrower > 0 {
+            transfer_tokens_exact(
+                ctx.accounts.bidder_usdc.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.borrower_usdc,
+                ctx.accounts.bidder.to_account_info(),
+                ctx.remaining_accounts,
+                to_borrower,
+            )?;
+        }
+
+        let loan = &ctx.accounts.loan;
+        let loan_id_bytes = loan.id.to_le_bytes();
+        let loan_seeds = &[
+            b"loan",
+            loan.mint.as_ref(),
+            loan_id_bytes.as_ref(),
+            &[loan.bump],
+        ];
+        transfer_tokens(
+            ctx.accounts.position_account.to_account_info(),
+            &ctx.accounts.asset_mint,
+            &mut ctx.accounts.bidder_asset,
+            ctx.accounts.loan.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[&loan_seeds[..]],
+        )?;
+
+        ctx.accounts.position_account.reload()?;
+        let position_emptied = ctx.accounts.position_account.amount == 0;
+        let loan = &mut ctx.accounts.loan;
+        loan.auction_debt_remaining -= to_lender;
+        loan.recovered_amount = loan.recovered_amount
+            .checked_add(to_lender)
+            .ok_or(CreditMarketError::MathOverflow)?;
+        if position_emptied {
+            loan.positions.retain(|p| p.token_account != position_account_key);
+            if loan.positions.is_empty() {
+                loan.auction_start = 0;
+            }
+        }
+
+        emit!(LiquidationAuctionBid {
+            loan_id: loan.id,
+            bidder: ctx.accounts.bidder.key(),
+            asset_mint: position.mint,
+            amount,
+            payment,
+            price_bps: price_bps as u16,
+            to_lender,
+            to_borrower,
+            debt_remaining: loan.auction_debt_remaining,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
     /// Mark a loan as defaulted (alternative to liquidation, used for accounting)
-    pub fn mark_default(ctx: Context<MarkDefault>) -> Result<()> {
+    /// Any collateral goes to the lender and counts towards its recovery.
+    pub fn mark_default<'info>(ctx: Context<'_, '_, '_, 'info, MarkDefault<'info>>) -> Result<()> {
         let loan = &mut ctx.accounts.loan;
-        require!(loan.status == LoanStatus::Active, CreditMarketError::LoanNotActive);
+        require!(loan.is_outstanding(), CreditMarketError::LoanNotActive);
 
         let clock = Clock::get()?;
         require!(
@@ -479,6 +1274,46 @@ pub mod credit_market {
         );
 
         loan.status = LoanStatus::Defaulted;
+        close_loan_borrow(loan, &mut ctx.accounts.global_state, clock.unix_timestamp)?;
+
+        // Seized collateral counts towards the lender's recovery at its oracle value
+        let collateral_value = match loan
+            .positions
+            .iter()
+            .find(|p| loan.collateral_amount > 0 && p.token_account == loan.collateral_vault)
+        {
+            Some(position) => {
+                let collateral_vault = ctx.accounts.collateral_vault
+                    .as_ref()
+                    .ok_or(CreditMarketError::CollateralAccountsMissing)?;
+                let price_feed = ctx.accounts.collateral_price_feed
+                    .as_ref()
+                    .ok_or(CreditMarketError::CollateralAccountsMissing)?;
+                oracle_value(
+                    &ctx.accounts.global_state.price_feeds,
+                    &position.mint,
+                    price_feed,
+                    collateral_vault.amount,
+                    position.decimals,
+                    ctx.accounts.mint.decimals,
+                    clock.unix_timestamp,
+                )?
+            }
+            None => 0,
+        };
+
+        release_collateral(
+            loan,
+            ctx.accounts.collateral_vault.as_ref(),
+            ctx.accounts.collateral_mint.as_ref(),
+            ctx.accounts.lender_collateral.as_mut(),
+            ctx.remaining_accounts,
+            true,
+        )?;
+        let loan = &mut ctx.accounts.loan;
+        loan.recovered_amount = loan.recovered_amount
+            .checked_add(collateral_value)
+            .ok_or(CreditMarketError::MathOverflow)?;
 
         // Penalize borrower reputation
         let borrower_profile = &mut ctx.accounts.borrower_profile;
@@ -488,6 +1323,8 @@ pub mod credit_market {
             .checked_add(1)
             .ok_or(CreditMarketError::MathOverflow)?;
 
+        record_default_for_breaker(&mut ctx.accounts.global_state, clock.unix_timestamp)?;
+
         emit!(LoanDefaulted {
             loan_id: loan.id,
             borrower: loan.borrower,
@@ -500,6 +1337,7 @@ pub mod credit_market {
     }
 
     /// Add a program to the whitelist (admin only)
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
     pub fn add_whitelisted_program(
         ctx: Context<AdminAction>,
         program_id: Pubkey,
@@ -509,15 +1347,17 @@ pub mod credit_market {
             global_state.admin == ctx.accounts.admin.key(),
             CreditMarketError::Unauthorized
         );
+        require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
 
-        if !global_state.whitelisted_programs.contains(&program_id) {
-            global_state.whitelisted_programs.push(program_id);
-        }
-
-        Ok(())
+        apply_governance_action(
+            global_state,
+            &GovernanceAction::AddWhitelistedProgram { program_id },
+            Clock::get()?.unix_timestamp,
+        )
     }
 
     /// Remove a program from the whitelist (admin only)
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
     pub fn remove_whitelisted_program(
         ctx: Context<AdminAction>,
         program_id: Pubkey,
@@ -527,28 +1367,138 @@ pub mod credit_market {
             global_state.admin == ctx.accounts.admin.key(),
             CreditMarketError::Unauthorized
         );
+        require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
 
-        global_state.whitelisted_programs.retain(|&p| p != program_id);
+        apply_governance_action(
+            global_state,
+            &GovernanceAction::RemoveWhitelistedProgram { program_id },
+            Clock::get()?.unix_timestamp,
+        )
+    }
 
-        Ok(())
+    /// Set the oracle feed used to value positions in `feed.mint` (admin only)
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
+    pub fn set_price_feed(ctx: Context<AdminAction>, feed: PriceFeed) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
+
+        let action = GovernanceAction::SetPriceFeed { feed };
+        validate_governance_action(&action)?;
+        apply_governance_action(global_state, &action, Clock::get()?.unix_timestamp)
     }
 
-    /// Claim insurance payout for a defaulted loan
-    /// Lenders can claim partial recovery from the insurance pool for defaulted loans
-    pub fn claim_insurance(ctx: Context<ClaimInsurance>) -> Result<()> {
-        let loan = &mut ctx.accounts.loan;
+    /// Remove the oracle feed for a mint (admin only)
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
+    pub fn remove_price_feed(ctx: Context<AdminAction>, mint: Pubkey) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
+
+        apply_governance_action(
+            global_state,
+            &GovernanceAction::RemovePriceFeed { mint },
+            Clock::get()?.unix_timestamp,
+        )
+    }
+
+    /// Set the kinked utilization curve variable-rate loans accrue at (admin only)
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
+    pub fn set_rate_model(ctx: Context<AdminAction>, model: RateModel) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
+
+        let action = GovernanceAction::SetRateModel { model };
+        validate_governance_action(&action)?;
+        apply_governance_action(global_state, &action, Clock::get()?.unix_timestamp)
+    }
+
+    /// Track a token account owned by the loan as a position, so its oracle value counts
+    /// towards the loan's health (borrower only)
+    pub fn add_loan_position(ctx: Context<AddLoanPosition>) -> Result<()> {
+        let global_state = &ctx.accounts.global_state;
+        let position_mint = ctx.accounts.position_mint.key();
+        let (credit_mint, _) = Pubkey::find_program_address(
+            &[b"credit_mint", global_state.key().as_ref()],
+            ctx.program_id,
+        );
+        require!(position_mint != credit_mint, CreditMarketError::InvalidPosition);
         require!(
-            loan.status == LoanStatus::Defaulted || loan.status == LoanStatus::Liquidated,
-            CreditMarketError::LoanNotDefaulted
+            global_state.price_feeds.iter().any(|f| f.mint == position_mint),
+            CreditMarketError::PriceFeedNotConfigured
         );
+
+        let loan = &mut ctx.accounts.loan;
+        let token_account = ctx.accounts.position_token.key();
         require!(
-            loan.lender == ctx.accounts.lender.key(),
-            CreditMarketError::Unauthorized
+            loan.positions.len() < MAX_LOAN_POSITIONS,
+            CreditMarketError::TooManyPositions
+        );
+        require!(
+            !loan.positions.iter().any(|p| p.token_account == token_account),
+            CreditMarketError::PositionAlreadyTracked
+        );
+        loan.positions.push(LoanPosition {
+            token_account,
+            mint: position_mint,
+            decimals: ctx.accounts.position_mint.decimals,
+        });
+
+        emit!(LoanPositionUpdated {
+            loan_id: loan.id,
+            token_account,
+            mint: position_mint,
+            removed: false,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Stop tracking an empty position (borrower only)
+    pub fn remove_loan_position(ctx: Context<RemoveLoanPosition>) -> Result<()> {
+        require!(
+            ctx.accounts.position_token.amount == 0,
+            CreditMarketError::PositionNotEmpty
+        );
+
+        let loan = &mut ctx.accounts.loan;
+        let token_account = ctx.accounts.position_token.key();
+        let index = loan.positions
+            .iter()
+            .position(|p| p.token_account == token_account)
+            .ok_or(CreditMarketError::InvalidPosition)?;
+        let position = loan.positions.remove(index);
+
+        emit!(LoanPositionUpdated {
+            loan_id: loan.id,
+            token_account,
+            mint: position.mint,
+            removed: true,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Claim insurance payout for a defaulted loan
+    /// Lenders can claim partial recovery from the insurance pool for defaulted loans
+    pub fn claim_insurance<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimInsurance<'info>>,
+    ) -> Result<()> {
+        let loan = &mut ctx.accounts.loan;
+        require!(
+            loan.status == LoanStatus::Defaulted || loan.status == LoanStatus::Liquidated,
+            CreditMarketError::LoanNotDefaulted
+        );
+        require!(
+            loan.lender == ctx.accounts.lender.key(),
+            CreditMarketError::Unauthorized
         );
         require!(
             !loan.insurance_claimed,
             CreditMarketError::InsuranceAlreadyClaimed
         );
+        // The lender's loss isn't known until the liquidation auction has finished
+        require!(loan.auction_start == 0, CreditMarketError::AuctionActive);
 
         let global_state = &mut ctx.accounts.global_state;
         let clock = Clock::get()?;
@@ -562,9 +1512,13 @@ pub mod credit_market {
             .checked_div(10000)
             .ok_or(CreditMarketError::MathOverflow)?;
 
+        // Never pay out more than the lender is still owed after any recoveries
+        let amount_due = calculate_amount_due_at_maturity(loan, global_state)?;
+        let remaining_loss = amount_due.saturating_sub(loan.recovered_amount);
+
         // Check available insurance pool balance
         let pool_balance = ctx.accounts.insurance_pool.amount;
-        let actual_payout = std::cmp::min(payout, pool_balance);
+        let actual_payout = payout.min(remaining_loss).min(pool_balance);
         
         require!(actual_payout > 0, CreditMarketError::InsufficientInsurancePool);
 
@@ -572,26 +1526,33 @@ pub mod credit_market {
         let bump = ctx.bumps.insurance_pool;
         let seeds = &[
             b"insurance_pool".as_ref(),
+            global_state.mint.as_ref(),
             &[bump],
         ];
         let signer_seeds = &[&seeds[..]];
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.insurance_pool.to_account_info(),
-            to: ctx.accounts.lender_usdc.to_account_info(),
-            authority: ctx.accounts.insurance_pool.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-        token::transfer(cpi_ctx, actual_payout)?;
+        transfer_tokens(
+            ctx.accounts.insurance_pool.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.lender_usdc,
+            ctx.accounts.insurance_pool.to_account_info(),
+            ctx.remaining_accounts,
+            actual_payout,
+            signer_seeds,
+        )?;
 
         // Mark insurance as claimed
         loan.insurance_claimed = true;
+        loan.insurance_payout = actual_payout;
 
         // Track total insurance claimed
         global_state.total_insurance_claimed = global_state.total_insurance_claimed
             .checked_add(actual_payout)
             .ok_or(CreditMarketError::MathOverflow)?;
+        global_state.solvency_ratio_bps = calculate_solvency_ratio_bps(
+            global_state.total_insurance_collected,
+            global_state.total_insurance_claimed,
+        )?;
 
         emit!(InsuranceClaimed {
             loan_id: loan.id,
@@ -605,243 +1566,4059 @@ pub mod credit_market {
         Ok(())
     }
 
+    /// Repay a defaulted loan after the fact (insurance subrogation)
+    /// Recoveries first cover the lender's uncovered loss, then reimburse the insurance
+    /// pool for whatever it paid out. Once both are made whole the loan is `Recovered`.
+    pub fn repay_defaulted_loan<'info>(
+        ctx: Context<'_, '_, '_, 'info, RepayDefaultedLoan<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, CreditMarketError::InvalidAmount);
+
+        let loan = &mut ctx.accounts.loan;
+        require!(loan.status == LoanStatus::Defaulted, CreditMarketError::LoanNotDefaulted);
+        require!(
+            loan.borrower == ctx.accounts.borrower.key(),
+            CreditMarketError::Unauthorized
+        );
+
+        let clock = Clock::get()?;
+        let global_state = &mut ctx.accounts.global_state;
+
+        let amount_due = calculate_amount_due_at_maturity(loan, global_state)?;
+        let (to_lender, to_pool) = split_recovery(
+            amount,
+            amount_due,
+            loan.insurance_payout,
+            loan.recovered_amount,
+        )?;
+        let total_recovered = to_lender
+            .checked_add(to_pool)
+            .ok_or(CreditMarketError::MathOverflow)?;
+        require!(total_recovered > 0, CreditMarketError::NothingToRecover);
+
+        // Lender's uncovered loss is repaid first
+        if to_lender > 0 {
+            transfer_tokens_exact(
+                ctx.accounts.borrower_usdc.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.lender_usdc,
+                ctx.accounts.borrower.to_account_info(),
+                ctx.remaining_accounts,
+                to_lender,
+            )?;
+        }
+
+        // Then the insurance pool is reimbursed for its payout
+        if to_pool > 0 {
+            transfer_tokens_exact(
+                ctx.accounts.borrower_usdc.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.insurance_pool,
+                ctx.accounts.borrower.to_account_info(),
+                ctx.remaining_accounts,
+                to_pool,
+            )?;
+
+            // Insurance claimed is tracked net of recoveries
+            global_state.total_insurance_claimed = global_state.total_insurance_claimed
+                .checked_sub(to_pool)
+                .ok_or(CreditMarketError::MathOverflow)?;
+            global_state.solvency_ratio_bps = calculate_solvency_ratio_bps(
+                global_state.total_insurance_collected,
+                global_state.total_insurance_claimed,
+            )?;
+        }
+
+        loan.recovered_amount = loan.recovered_amount
+            .checked_add(total_recovered)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        let fully_recovered = loan.recovered_amount >= amount_due;
+        if fully_recovered {
+            loan.status = LoanStatus::Recovered;
+        }
+
+        emit!(DefaultedLoanRepaid {
+            loan_id: loan.id,
+            borrower: loan.borrower,
+            lender: loan.lender,
+            amount: total_recovered,
+            to_lender,
+            to_insurance_pool: to_pool,
+            total_recovered: loan.recovered_amount,
+            fully_recovered,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
     /// Update insurance and protocol fee rates (admin only)
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
     pub fn update_fee_rates(
         ctx: Context<AdminAction>,
         new_insurance_fee_bps: Option<u16>,
         new_protocol_fee_bps: Option<u16>,
     ) -> Result<()> {
         let global_state = &mut ctx.accounts.global_state;
-        
-        if let Some(insurance_bps) = new_insurance_fee_bps {
-            require!(insurance_bps <= 2000, CreditMarketError::InvalidFeeRate); // Max 20%
-            global_state.insurance_fee_bps = insurance_bps;
-        }
-        
-        if let Some(protocol_bps) = new_protocol_fee_bps {
-            require!(protocol_bps <= 500, CreditMarketError::InvalidFeeRate); // Max 5%
-            global_state.fee_bps = protocol_bps;
-        }
+        require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
+
+        let action = GovernanceAction::UpdateFeeRates {
+            insurance_fee_bps: new_insurance_fee_bps,
+            protocol_fee_bps: new_protocol_fee_bps,
+        };
+        validate_governance_action(&action)?;
+        apply_governance_action(global_state, &action, Clock::get()?.unix_timestamp)
+    }
+
+    /// Propose a new admin (admin only)
+    /// The handover completes when the proposed admin calls `accept_admin`.
+    /// Proposing `Pubkey::default()` cancels a pending handover.
+    pub fn propose_admin(ctx: Context<AdminAction>, new_admin: Pubkey) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.pending_admin = new_admin;
 
-        emit!(FeeRatesUpdated {
-            insurance_fee_bps: global_state.insurance_fee_bps,
-            protocol_fee_bps: global_state.fee_bps,
+        emit!(AdminProposed {
+            admin: global_state.admin,
+            pending_admin: new_admin,
             timestamp: Clock::get()?.unix_timestamp,
         });
 
         Ok(())
     }
-}
 
-// ============================================================================
-// Helper Functions
-// ============================================================================
+    /// Accept a pending admin handover (proposed admin only)
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let previous_admin = global_state.admin;
+        global_state.admin = ctx.accounts.new_admin.key();
+        global_state.pending_admin = Pubkey::default();
 
-/// Calculate simple interest: principal * rate * duration / (365 days * 10000)
-fn calculate_interest(principal: u64, rate_bps: u16, duration_secs: u64) -> Result<u64> {
-    const SECONDS_PER_YEAR: u128 = 365 * 24 * 60 * 60;
-    
-    let interest = (principal as u128)
-        .checked_mul(rate_bps as u128)
-        .ok_or(CreditMarketError::MathOverflow)?
-        .checked_mul(duration_secs as u128)
-        .ok_or(CreditMarketError::MathOverflow)?
-        .checked_div(SECONDS_PER_YEAR)
-        .ok_or(CreditMarketError::MathOverflow)?
-        .checked_div(10000)
-        .ok_or(CreditMarketError::MathOverflow)?;
-    
-    Ok(interest as u64)
-}
+        emit!(AdminTransferred {
+            previous_admin,
+            new_admin: global_state.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-// ============================================================================
-// Account Contexts
-// ============================================================================
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(
-        init,
-        payer = admin,
-        space = 8 + GlobalState::INIT_SPACE,
-        seeds = [b"global_state"],
-        bump,
-    )]
-    pub global_state: Account<'info, GlobalState>,
-    pub system_program: Program<'info, System>,
-}
+    /// Queue a fee, whitelist or timelock change (admin only)
+    /// The action can be executed by anyone once `timelock_delay_secs` has passed,
+    /// giving lenders time to exit before it lands.
+    pub fn queue_action(ctx: Context<QueueAction>, action: GovernanceAction) -> Result<()> {
+        validate_governance_action(&action)?;
 
-#[derive(Accounts)]
-pub struct PostLendOffer<'info> {
-    #[account(mut)]
-    pub lender: Signer<'info>,
-    #[account(
-        init,
-        payer = lender,
-        space = 8 + LendOffer::INIT_SPACE,
-        seeds = [b"offer", lender.key().as_ref(), global_state.next_loan_id.to_le_bytes().as_ref()],
-        bump,
-    )]
-    pub offer: Account<'info, LendOffer>,
-    #[account(mut)]
-    pub lender_usdc: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        seeds = [b"escrow", lender.key().as_ref()],
-        bump,
-    )]
-    pub escrow_vault: Account<'info, TokenAccount>,
-    pub usdc_mint: Account<'info, anchor_spl::token::Mint>,
-    #[account(
-        mut,
-        seeds = [b"global_state"],
-        bump = global_state.bump,
-    )]
-    pub global_state: Account<'info, GlobalState>,
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
+        let clock = Clock::get()?;
+        let global_state = &mut ctx.accounts.global_state;
+        let action_id = global_state.next_action_id;
+        global_state.next_action_id = action_id.checked_add(1)
+            .ok_or(CreditMarketError::MathOverflow)?;
 
-#[derive(Accounts)]
-pub struct CancelLendOffer<'info> {
-    #[account(mut)]
-    pub lender: Signer<'info>,
-    #[account(
-        mut,
-        constraint = offer.lender == lender.key() @ CreditMarketError::Unauthorized,
-    )]
-    pub offer: Account<'info, LendOffer>,
-    #[account(mut)]
-    pub lender_usdc: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        seeds = [b"escrow", lender.key().as_ref()],
-        bump,
-    )]
-    pub escrow_vault: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
-}
+        let eta = clock.unix_timestamp
+            .checked_add(global_state.timelock_delay_secs)
+            .ok_or(CreditMarketError::MathOverflow)?;
 
-#[derive(Accounts)]
-pub struct PostBorrowRequest<'info> {
-    #[account(mut)]
-    pub borrower: Signer<'info>,
-    #[account(
-        init,
-        payer = borrower,
-        space = 8 + BorrowRequest::INIT_SPACE,
-        seeds = [b"request", borrower.key().as_ref()],
-        bump,
-    )]
-    pub request: Account<'info, BorrowRequest>,
-    pub system_program: Program<'info, System>,
-}
+        let queued_action = &mut ctx.accounts.queued_action;
+        queued_action.id = action_id;
+        queued_action.proposer = ctx.accounts.admin.key();
+        queued_action.action = action.clone();
+        queued_action.queued_at = clock.unix_timestamp;
+        queued_action.eta = eta;
+        queued_action.bump = ctx.bumps.queued_action;
 
-#[derive(Accounts)]
-pub struct AcceptLendOffer<'info> {
-    #[account(mut)]
-    pub borrower: Signer<'info>,
-    #[account(
-        mut,
-        constraint = offer.is_active @ CreditMarketError::OfferNotActive,
-    )]
-    pub offer: Account<'info, LendOffer>,
-    pub borrower_profile: Account<'info, BorrowerProfile>,
-    #[account(
-        init,
-        payer = borrower,
-        space = 8 + Loan::INIT_SPACE,
-        seeds = [b"loan", global_state.next_loan_id.to_le_bytes().as_ref()],
-        bump,
-    )]
-    pub loan: Account<'info, Loan>,
-    #[account(
-        mut,
-        seeds = [b"loan_vault", global_state.next_loan_id.to_le_bytes().as_ref()],
-        bump,
-    )]
-    pub loan_vault: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        seeds = [b"escrow", offer.lender.as_ref()],
-        bump,
-    )]
-    pub escrow_vault: Account<'info, TokenAccount>,
-    pub usdc_mint: Account<'info, anchor_spl::token::Mint>,
-    #[account(
-        mut,
-        seeds = [b"global_state"],
-        bump = global_state.bump,
-    )]
-    pub global_state: Account<'info, GlobalState>,
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
+        emit!(ActionQueued {
+            action_id,
+            action,
+            eta,
+            timestamp: clock.unix_timestamp,
+        });
 
-#[derive(Accounts)]
-pub struct RepayLoan<'info> {
-    #[account(mut)]
-    pub borrower: Signer<'info>,
-    #[account(
-        mut,
-        constraint = loan.borrower == borrower.key() @ CreditMarketError::Unauthorized,
-        constraint = loan.status == LoanStatus::Active @ CreditMarketError::LoanNotActive,
-    )]
-    pub loan: Account<'info, Loan>,
-    #[account(mut)]
-    pub borrower_usdc: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        constraint = lender_usdc.owner == loan.lender,
-    )]
-    pub lender_usdc: Account<'info, TokenAccount>,
-    /// Insurance pool receives 10% of interest
-    #[account(
-        mut,
-        constraint = insurance_pool.key() == global_state.insurance_pool @ CreditMarketError::InvalidInsurancePool,
-    )]
-    pub insurance_pool: Account<'info, TokenAccount>,
-    /// Treasury receives 1% of interest (protocol fee)
-    #[account(
-        mut,
-        constraint = treasury.key() == global_state.treasury @ CreditMarketError::InvalidTreasury,
-    )]
-    pub treasury: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        seeds = [b"global_state"],
-        bump = global_state.bump,
-    )]
-    pub global_state: Account<'info, GlobalState>,
-    #[account(mut)]
-    pub borrower_profile: Account<'info, BorrowerProfile>,
-    pub token_program: Program<'info, Token>,
-}
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct ExecuteTrade<'info> {
-    pub borrower: Signer<'info>,
-    #[account(
-        constraint = loan.borrower == borrower.key() @ CreditMarketError::Unauthorized,
-        constraint = loan.status == LoanStatus::Active @ CreditMarketError::LoanNotActive,
-    )]
-    pub loan: Account<'info, Loan>,
-    #[account(mut)]
-    pub loan_vault: Account<'info, TokenAccount>,
-    /// CHECK: Target program for CPI
-    pub target_program: UncheckedAccount<'info>,
+    /// Execute a queued action once its timelock has elapsed (permissionless)
+    pub fn execute_action(ctx: Context<ExecuteAction>) -> Result<()> {
+        let clock = Clock::get()?;
+        let queued_action = &ctx.accounts.queued_action;
+        require!(
+            clock.unix_timestamp >= queued_action.eta,
+            CreditMarketError::TimelockNotElapsed
+        );
+
+        let global_state = &mut ctx.accounts.global_state;
+        apply_governance_action(global_state, &queued_action.action, clock.unix_timestamp)?;
+
+        emit!(ActionExecuted {
+            action_id: queued_action.id,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a queued action before it is executed (admin, or the proposer of an
+    /// approved `MultisigAction::CancelAction` while a multisig is admin)
+    pub fn cancel_action(ctx: Context<CancelAction>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        authorize_admin(
+            &accounts.global_state,
+            &accounts.authority,
+            accounts.multisig.as_ref(),
+            accounts.approval.as_ref(),
+            &MultisigAction::CancelAction { action_id: accounts.queued_action.id },
+        )?;
+
+        emit!(ActionCancelled {
+            action_id: ctx.accounts.queued_action.id,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Create an M-of-N signer set that can act as the market admin
+    /// Hand admin over to it with `propose_admin(multisig)` followed by an
+    /// approved `MultisigAction::AcceptAdmin` proposal.
+    pub fn create_multisig(
+        ctx: Context<CreateMultisig>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        validate_multisig_signers(&signers, threshold)?;
+
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.creator = ctx.accounts.creator.key();
+        multisig.signers = signers.clone();
+        multisig.threshold = threshold;
+        multisig.owner_set_seqno = 0;
+        multisig.next_proposal_id = 1;
+        multisig.bump = ctx.bumps.multisig;
+
+        emit!(MultisigCreated {
+            multisig: multisig.key(),
+            signers,
+            threshold,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Propose an admin action on behalf of the multisig (multisig signer only)
+    /// The proposer's approval is counted immediately.
+    pub fn propose_multisig_action(
+        ctx: Context<ProposeMultisigAction>,
+        action: MultisigAction,
+    ) -> Result<()> {
+        match &action {
+            MultisigAction::Governance(governance_action) => {
+                validate_governance_action(governance_action)?
+            }
+            MultisigAction::ChangeSigners { signers, threshold } => {
+                validate_multisig_signers(signers, *threshold)?
+            }
+            MultisigAction::InitializeCreditWrapper { transfer_hook_program } => {
+                require!(
+                    *transfer_hook_program != crate::ID,
+                    CreditMarketError::InvalidTransferHookProgram
+                );
+            }
+            MultisigAction::ProposeAdmin { .. }
+            | MultisigAction::AcceptAdmin
+            | MultisigAction::CancelAction { .. }
+            | MultisigAction::AddRedemptionAddress { .. }
+            | MultisigAction::RemoveRedemptionAddress { .. }
+            | MultisigAction::RevokeMerchant { .. } => {}
+            MultisigAction::ApproveMerchant { label, .. } => {
+                require!(label.len() <= MAX_MERCHANT_LABEL_LEN, CreditMarketError::LabelTooLong);
+            }
+        }
+
+        let clock = Clock::get()?;
+        let multisig = &mut ctx.accounts.multisig;
+        let proposal_id = multisig.next_proposal_id;
+        multisig.next_proposal_id = proposal_id.checked_add(1)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.multisig = multisig.key();
+        proposal.market = ctx.accounts.global_state.key();
+        proposal.id = proposal_id;
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.action = action.clone();
+        proposal.approvals = vec![ctx.accounts.proposer.key()];
+        proposal.owner_set_seqno = multisig.owner_set_seqno;
+        proposal.created_at = clock.unix_timestamp;
+        proposal.eta = 0;
+        proposal.bump = ctx.bumps.proposal;
+
+        if proposal.approvals.len() >= multisig.threshold as usize {
+            proposal.eta = clock.unix_timestamp
+                .checked_add(multisig_action_delay(&ctx.accounts.global_state, &action))
+                .ok_or(CreditMarketError::MathOverflow)?;
+        }
+
+        emit!(MultisigActionProposed {
+            multisig: proposal.multisig,
+            proposal_id,
+            proposer: proposal.proposer,
+            action,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Approve a pending multisig proposal (multisig signer only)
+    /// Once approvals reach the threshold the proposal becomes executable, after the
+    /// governance timelock for fee and whitelist changes.
+    pub fn approve_multisig_action(ctx: Context<ApproveMultisigAction>) -> Result<()> {
+        let clock = Clock::get()?;
+        let multisig = &ctx.accounts.multisig;
+        let proposal = &mut ctx.accounts.proposal;
+        let approver = ctx.accounts.approver.key();
+        require!(
+            !proposal.approvals.contains(&approver),
+            CreditMarketError::AlreadyApproved
+        );
+
+        proposal.approvals.push(approver);
+
+        if proposal.eta == 0 && proposal.approvals.len() >= multisig.threshold as usize {
+            proposal.eta = clock.unix_timestamp
+                .checked_add(multisig_action_delay(&ctx.accounts.global_state, &proposal.action))
+                .ok_or(CreditMarketError::MathOverflow)?;
+        }
+
+        emit!(MultisigActionApproved {
+            multisig: multisig.key(),
+            proposal_id: proposal.id,
+            approver,
+            approvals: proposal.approvals.len() as u8,
+            threshold: multisig.threshold,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Execute an approved multisig proposal (permissionless once executable)
+    pub fn execute_multisig_action(ctx: Context<ExecuteMultisigAction>) -> Result<()> {
+        let clock = Clock::get()?;
+        let proposal = &ctx.accounts.proposal;
+        require!(proposal.eta != 0, CreditMarketError::ThresholdNotMet);
+        require!(
+            clock.unix_timestamp >= proposal.eta,
+            CreditMarketError::TimelockNotElapsed
+        );
+
+        let multisig_key = ctx.accounts.multisig.key();
+        let global_state = &mut ctx.accounts.global_state;
+
+        match &proposal.action {
+            MultisigAction::Governance(governance_action) => {
+                require!(global_state.admin == multisig_key, CreditMarketError::Unauthorized);
+                apply_governance_action(global_state, governance_action, clock.unix_timestamp)?;
+            }
+            MultisigAction::ProposeAdmin { new_admin } => {
+                require!(global_state.admin == multisig_key, CreditMarketError::Unauthorized);
+                global_state.pending_admin = *new_admin;
+
+                emit!(AdminProposed {
+                    admin: global_state.admin,
+                    pending_admin: *new_admin,
+                    timestamp: clock.unix_timestamp,
+                });
+            }
+            MultisigAction::AcceptAdmin => {
+                require!(
+                    global_state.pending_admin == multisig_key,
+                    CreditMarketError::Unauthorized
+                );
+                let previous_admin = global_state.admin;
+                global_state.admin = multisig_key;
+                global_state.pending_admin = Pubkey::default();
+
+                emit!(AdminTransferred {
+                    previous_admin,
+                    new_admin: multisig_key,
+                    timestamp: clock.unix_timestamp,
+                });
+            }
+            MultisigAction::ChangeSigners { signers, threshold } => {
+                let multisig = &mut ctx.accounts.multisig;
+                multisig.signers = signers.clone();
+                multisig.threshold = *threshold;
+                // Invalidates approvals on every other outstanding proposal
+                multisig.owner_set_seqno = multisig.owner_set_seqno
+                    .checked_add(1)
+                    .ok_or(CreditMarketError::MathOverflow)?;
+            }
+            MultisigAction::CancelAction { .. }
+            | MultisigAction::InitializeCreditWrapper { .. }
+            | MultisigAction::AddRedemptionAddress { .. }
+            | MultisigAction::RemoveRedemptionAddress { .. }
+            | MultisigAction::ApproveMerchant { .. }
+            | MultisigAction::RevokeMerchant { .. } => {
+                return err!(CreditMarketError::ApprovalOnlyAction);
+            }
+        }
+
+        emit!(MultisigActionExecuted {
+            multisig: multisig_key,
+            proposal_id: proposal.id,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a multisig proposal before it is executed (proposer only)
+    pub fn cancel_multisig_action(ctx: Context<CancelMultisigAction>) -> Result<()> {
+        emit!(MultisigActionCancelled {
+            multisig: ctx.accounts.proposal.multisig,
+            proposal_id: ctx.accounts.proposal.id,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Update the insurance premium curve (admin only)
+    /// Premiums rise above `insurance_fee_bps` when the pool's solvency ratio is below target,
+    /// fall when it is above, and are clamped to [min, max] before the tier discount applies.
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
+    pub fn update_premium_curve(
+        ctx: Context<AdminAction>,
+        min_premium_bps: Option<u16>,
+        max_premium_bps: Option<u16>,
+        target_solvency_bps: Option<u32>,
+        tier_discount_bps: Option<u16>,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
+
+        let action = GovernanceAction::UpdatePremiumCurve {
+            min_premium_bps,
+            max_premium_bps,
+            target_solvency_bps,
+            tier_discount_bps,
+        };
+        validate_governance_action(&action)?;
+        apply_governance_action(global_state, &action, Clock::get()?.unix_timestamp)
+    }
+
+    /// Set the account allowed to pause and unpause instructions (admin only)
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
+    pub fn set_pause_authority(
+        ctx: Context<AdminAction>,
+        pause_authority: Pubkey,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
+
+        let action = GovernanceAction::SetPauseAuthority { pause_authority };
+        validate_governance_action(&action)?;
+        apply_governance_action(global_state, &action, Clock::get()?.unix_timestamp)
+    }
+
+    /// Pause or unpause instructions (pause authority or admin)
+    /// `paused_flags` replaces the current bitflags, see `PAUSE_*`
+    pub fn set_paused(ctx: Context<PauseAction>, paused_flags: u8) -> Result<()> {
+        require!(paused_flags & !PAUSE_ALL == 0, CreditMarketError::InvalidPauseFlags);
+
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.paused_flags = paused_flags;
+
+        emit!(PauseFlagsUpdated {
+            authority: ctx.accounts.authority.key(),
+            paused_flags,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Configure the default circuit breaker (admin only)
+    /// Trips when more than `max_defaults` loans default or are liquidated within any
+    /// rolling `window_secs`, pausing new offers, new loans and trades. `max_defaults` is at
+    /// most `MAX_BREAKER_DEFAULTS`. A zero window disables it.
+    /// Only available while the timelock is disabled, otherwise use `queue_action`
+    pub fn configure_circuit_breaker(
+        ctx: Context<AdminAction>,
+        window_secs: i64,
+        max_defaults: u32,
+    ) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
+
+        let action = GovernanceAction::ConfigureCircuitBreaker { window_secs, max_defaults };
+        validate_governance_action(&action)?;
+        apply_governance_action(global_state, &action, Clock::get()?.unix_timestamp)
+    }
+
+    /// Create the market's credit token and reserve (admin, or the proposer of an approved
+    /// `MultisigAction::InitializeCreditWrapper` while a multisig is admin)
+    /// The credit mint is a Token-2022 mint with the market's decimals, whose transfers are
+    /// checked by `transfer_hook_program`. The wrapper is its mint authority and permanent
+    /// delegate, and registers the hook's extra account metas for it.
+    pub fn initialize_credit_wrapper(ctx: Context<InitializeCreditWrapper>) -> Result<()> {
+        let market = ctx.accounts.global_state.key();
+        let wrapper_key = ctx.accounts.credit_wrapper.key();
+        let credit_mint_key = ctx.accounts.credit_mint.key();
+        let credit_token_program_id = ctx.accounts.credit_token_program.key();
+        let hook_program_id = ctx.accounts.transfer_hook_program.key();
+        require!(
+            hook_program_id != crate::ID,
+            CreditMarketError::InvalidTransferHookProgram
+        );
+        authorize_admin(
+            &ctx.accounts.global_state,
+            &ctx.accounts.authority,
+            ctx.accounts.multisig.as_ref(),
+            ctx.accounts.approval.as_ref(),
+            &MultisigAction::InitializeCreditWrapper { transfer_hook_program: hook_program_id },
+        )?;
+
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::PermanentDelegate,
+            ExtensionType::TransferHook,
+        ])?;
+        let credit_mint_seeds = &[b"credit_mint", market.as_ref(), &[ctx.bumps.credit_mint]];
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::CreateAccount {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.credit_mint.to_account_info(),
+                },
+                &[&credit_mint_seeds[..]],
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &credit_token_program_id,
+        )?;
+
+        // Extensions must be initialized before the mint itself
+        let credit_mint_info = ctx.accounts.credit_mint.to_account_info();
+        invoke(
+            &spl_token_2022::instruction::initialize_permanent_delegate(
+                &credit_token_program_id,
+                &credit_mint_key,
+                &wrapper_key,
+            )?,
+            std::slice::from_ref(&credit_mint_info),
+        )?;
+        invoke(
+            &transfer_hook::instruction::initialize(
+                &credit_token_program_id,
+                &credit_mint_key,
+                Some(wrapper_key),
+                Some(hook_program_id),
+            )?,
+            std::slice::from_ref(&credit_mint_info),
+        )?;
+        invoke(
+            &spl_token_2022::instruction::initialize_mint2(
+                &credit_token_program_id,
+                &credit_mint_key,
+                &wrapper_key,
+                None,
+                ctx.accounts.mint.decimals,
+            )?,
+            std::slice::from_ref(&credit_mint_info),
+        )?;
+
+        // The hook only lets the mint authority create its account-meta list
+        let wrapper_seeds = &[b"credit_wrapper", market.as_ref(), &[ctx.bumps.credit_wrapper]];
+        invoke_signed(
+            &Instruction {
+                program_id: hook_program_id,
+                accounts: vec![
+                    AccountMeta::new(ctx.accounts.authority.key(), true),
+                    AccountMeta::new(ctx.accounts.extra_account_meta_list.key(), false),
+                    AccountMeta::new_readonly(credit_mint_key, false),
+                    AccountMeta::new_readonly(wrapper_key, true),
+                    AccountMeta::new_readonly(ctx.accounts.system_program.key(), false),
+                ],
+                data: hash(b"global:initialize_extra_account_meta_list").to_bytes()[..8].to_vec(),
+            },
+            &[
+                ctx.accounts.authority.to_account_info(),
+                ctx.accounts.extra_account_meta_list.to_account_info(),
+                credit_mint_info,
+                ctx.accounts.credit_wrapper.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.transfer_hook_program.to_account_info(),
+            ],
+            &[&wrapper_seeds[..]],
+        )?;
+
+        let credit_wrapper = &mut ctx.accounts.credit_wrapper;
+        credit_wrapper.market = market;
+        credit_wrapper.underlying_mint = ctx.accounts.mint.key();
+        credit_wrapper.credit_mint = credit_mint_key;
+        credit_wrapper.reserve = ctx.accounts.credit_reserve.key();
+        credit_wrapper.transfer_hook_program = hook_program_id;
+        credit_wrapper.total_wrapped = 0;
+        credit_wrapper.bump = ctx.bumps.credit_wrapper;
+
+        emit!(CreditWrapperInitialized {
+            market,
+            credit_mint: credit_mint_key,
+            reserve: credit_wrapper.reserve,
+            transfer_hook_program: hook_program_id,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Approve a token account that credit can be unwrapped to (admin, or the proposer of
+    /// an approved `MultisigAction::AddRedemptionAddress` while a multisig is admin)
+    pub fn add_redemption_address(
+        ctx: Context<AddRedemptionAddress>,
+        address: Pubkey,
+    ) -> Result<()> {
+        authorize_admin(
+            &ctx.accounts.global_state,
+            &ctx.accounts.authority,
+            ctx.accounts.multisig.as_ref(),
+            ctx.accounts.approval.as_ref(),
+            &MultisigAction::AddRedemptionAddress { address },
+        )?;
+
+        let clock = Clock::get()?;
+        let redemption_address = &mut ctx.accounts.redemption_address;
+        redemption_address.wrapper = ctx.accounts.credit_wrapper.key();
+        redemption_address.address = address;
+        redemption_address.added_at = clock.unix_timestamp;
+        redemption_address.bump = ctx.bumps.redemption_address;
+
+        emit!(RedemptionAddressUpdated {
+            wrapper: redemption_address.wrapper,
+            address,
+            approved: true,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke a redemption address (admin, or the proposer of an approved
+    /// `MultisigAction::RemoveRedemptionAddress` while a multisig is admin)
+    pub fn remove_redemption_address(ctx: Context<RemoveRedemptionAddress>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        authorize_admin(
+            &accounts.global_state,
+            &accounts.authority,
+            accounts.multisig.as_ref(),
+            accounts.approval.as_ref(),
+            &MultisigAction::RemoveRedemptionAddress {
+                address: accounts.redemption_address.address,
+            },
+        )?;
+
+        emit!(RedemptionAddressUpdated {
+            wrapper: ctx.accounts.redemption_address.wrapper,
+            address: ctx.accounts.redemption_address.address,
+            approved: false,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Deposit market USDC into the credit reserve and receive the same amount of credit
+    pub fn wrap<'info>(ctx: Context<'_, '_, '_, 'info, Wrap<'info>>, amount: u64) -> Result<()> {
+        require!(amount > 0, CreditMarketError::InvalidAmount);
+
+        // Credit is minted for what the reserve actually received
+        let received = transfer_tokens(
+            ctx.accounts.holder_usdc.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.credit_reserve,
+            ctx.accounts.holder.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[],
+        )?;
+        require!(received > 0, CreditMarketError::InvalidAmount);
+
+        let credit_wrapper = &mut ctx.accounts.credit_wrapper;
+        mint_credit(
+            credit_wrapper,
+            &ctx.accounts.credit_mint,
+            ctx.accounts.holder_credit.to_account_info(),
+            received,
+        )?;
+        credit_wrapper.total_wrapped = credit_wrapper.total_wrapped
+            .checked_add(received)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        emit!(CreditWrapped {
+            wrapper: credit_wrapper.key(),
+            holder: ctx.accounts.holder.key(),
+            amount: received,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Burn credit and release its USDC to an approved redemption address
+    pub fn unwrap<'info>(ctx: Context<'_, '_, '_, 'info, Unwrap<'info>>, amount: u64) -> Result<()> {
+        require!(amount > 0, CreditMarketError::InvalidAmount);
+
+        burn_credit(
+            &ctx.accounts.credit_mint,
+            ctx.accounts.holder_credit.to_account_info(),
+            ctx.accounts.holder.to_account_info(),
+            amount,
+            &[],
+        )?;
+        let credit_wrapper = &mut ctx.accounts.credit_wrapper;
+        release_credit_reserve(
+            credit_wrapper,
+            &ctx.accounts.mint,
+            ctx.accounts.credit_reserve.to_account_info(),
+            &mut ctx.accounts.destination,
+            ctx.remaining_accounts,
+            amount,
+        )?;
+        credit_wrapper.total_wrapped = credit_wrapper.total_wrapped
+            .checked_sub(amount)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        emit!(CreditUnwrapped {
+            wrapper: credit_wrapper.key(),
+            holder: ctx.accounts.holder.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Disburse loan funds as credit: USDC moves from the loan vault into the credit
+    /// reserve and the borrower receives the same amount of credit
+    pub fn disburse_loan_credit<'info>(
+        ctx: Context<'_, '_, '_, 'info, DisburseLoanCredit<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.global_state.is_paused(PAUSE_EXECUTE_TRADE),
+            CreditMarketError::InstructionPaused
+        );
+        require!(amount > 0, CreditMarketError::InvalidAmount);
+
+        // Liquidation claws credit back from a single account per loan
+        let loan = &ctx.accounts.loan;
+        require!(
+            loan.credit_account == Pubkey::default()
+                || loan.credit_account == ctx.accounts.borrower_credit.key(),
+            CreditMarketError::CreditAccountMismatch
+        );
+
+        let loan_mint = loan.mint;
+        let loan_id_bytes = loan.id.to_le_bytes();
+        let loan_seeds = &[
+            b"loan",
+            loan_mint.as_ref(),
+            loan_id_bytes.as_ref(),
+            &[loan.bump],
+        ];
+        let received = transfer_tokens(
+            ctx.accounts.loan_vault.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.credit_reserve,
+            ctx.accounts.loan.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[&loan_seeds[..]],
+        )?;
+        require!(received > 0, CreditMarketError::InvalidAmount);
+
+        let credit_wrapper = &mut ctx.accounts.credit_wrapper;
+        mint_credit(
+            credit_wrapper,
+            &ctx.accounts.credit_mint,
+            ctx.accounts.borrower_credit.to_account_info(),
+            received,
+        )?;
+        credit_wrapper.total_wrapped = credit_wrapper.total_wrapped
+            .checked_add(received)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        let loan = &mut ctx.accounts.loan;
+        loan.credit_account = ctx.accounts.borrower_credit.key();
+        loan.credit_outstanding = loan.credit_outstanding
+            .checked_add(received)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        emit!(LoanCreditDisbursed {
+            loan_id: loan.id,
+            borrower: loan.borrower,
+            amount: received,
+            credit_outstanding: loan.credit_outstanding,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Register a session key that can act on the borrower's loans until `expires_at`
+    /// `allowed_actions` is a set of SESSION_* flags. `spend_cap` bounds the total the key
+    /// can pay out of loan vaults over its lifetime.
+    pub fn register_session_key(
+        ctx: Context<RegisterSessionKey>,
+        key: Pubkey,
+        expires_at: i64,
+        allowed_actions: u8,
+        spend_cap: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            expires_at > now && expires_at - now <= MAX_SESSION_DURATION_SECS,
+            CreditMarketError::InvalidDuration
+        );
+        require!(
+            allowed_actions != 0 && allowed_actions & !SESSION_ALL == 0,
+            CreditMarketError::InvalidSessionKey
+        );
+        require!(key != ctx.accounts.borrower.key(), CreditMarketError::InvalidSessionKey);
+
+        let session = &mut ctx.accounts.session_key;
+        session.borrower = ctx.accounts.borrower.key();
+        session.key = key;
+        session.expires_at = expires_at;
+        session.allowed_actions = allowed_actions;
+        session.spend_cap = spend_cap;
+        session.spent = 0;
+        session.created_at = now;
+        session.bump = ctx.bumps.session_key;
+
+        emit!(SessionKeyRegistered {
+            borrower: session.borrower,
+            session_key: key,
+            expires_at,
+            allowed_actions,
+            spend_cap,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke a session key. The account is closed, so the key stops working immediately.
+    pub fn revoke_session_key(ctx: Context<RevokeSessionKey>) -> Result<()> {
+        emit!(SessionKeyRevoked {
+            borrower: ctx.accounts.borrower.key(),
+            session_key: ctx.accounts.session_key.key,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Register a merchant token account that loans can pay
+    /// Anyone but the admin can register, but `pay_merchant` only honours entries approved
+    /// by the paying loan's lender here, or by the admin through `approve_admin_merchant`.
+    pub fn approve_merchant(
+        ctx: Context<ApproveMerchant>,
+        label: String,
+    ) -> Result<()> {
+        require!(label.len() <= MAX_MERCHANT_LABEL_LEN, CreditMarketError::LabelTooLong);
+        require!(
+            ctx.accounts.approver.key() != ctx.accounts.global_state.admin,
+            CreditMarketError::Unauthorized
+        );
+
+        let clock = Clock::get()?;
+        let merchant = &mut ctx.accounts.merchant;
+        merchant.market = ctx.accounts.global_state.key();
+        merchant.approver = ctx.accounts.approver.key();
+        merchant.token_account = ctx.accounts.merchant_token.key();
+        merchant.label = label;
+        merchant.added_at = clock.unix_timestamp;
+        merchant.bump = ctx.bumps.merchant;
+
+        emit!(MerchantUpdated {
+            market: merchant.market,
+            approver: merchant.approver,
+            token_account: merchant.token_account,
+            approved: true,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke a merchant registry entry (approver only)
+    pub fn revoke_merchant(ctx: Context<RevokeMerchant>) -> Result<()> {
+        let merchant = &ctx.accounts.merchant;
+        emit!(MerchantUpdated {
+            market: merchant.market,
+            approver: merchant.approver,
+            token_account: merchant.token_account,
+            approved: false,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Approve a merchant token account for every loan in the market (admin, or the
+    /// proposer of an approved `MultisigAction::ApproveMerchant` while a multisig is admin)
+    /// A direct admin call is only available while the timelock is disabled; multisig
+    /// approvals wait out the timelock before they can be used.
+    pub fn approve_admin_merchant(
+        ctx: Context<ApproveAdminMerchant>,
+        label: String,
+    ) -> Result<()> {
+        require!(label.len() <= MAX_MERCHANT_LABEL_LEN, CreditMarketError::LabelTooLong);
+
+        let global_state = &ctx.accounts.global_state;
+        if global_state.admin == ctx.accounts.authority.key() {
+            require!(global_state.timelock_delay_secs == 0, CreditMarketError::TimelockActive);
+        }
+        authorize_admin(
+            global_state,
+            &ctx.accounts.authority,
+            ctx.accounts.multisig.as_ref(),
+            ctx.accounts.approval.as_ref(),
+            &MultisigAction::ApproveMerchant {
+                token_account: ctx.accounts.merchant_token.key(),
+                label: label.clone(),
+            },
+        )?;
+
+        let clock = Clock::get()?;
+        let merchant = &mut ctx.accounts.merchant;
+        merchant.market = global_state.key();
+        merchant.approver = global_state.admin;
+        merchant.token_account = ctx.accounts.merchant_token.key();
+        merchant.label = label;
+        merchant.added_at = clock.unix_timestamp;
+        merchant.bump = ctx.bumps.merchant;
+
+        emit!(MerchantUpdated {
+            market: merchant.market,
+            approver: merchant.approver,
+            token_account: merchant.token_account,
+            approved: true,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke an admin-approved merchant (admin, or the proposer of an approved
+    /// `MultisigAction::RevokeMerchant` while a multisig is admin)
+    pub fn revoke_admin_merchant(ctx: Context<RevokeAdminMerchant>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        authorize_admin(
+            &accounts.global_state,
+            &accounts.authority,
+            accounts.multisig.as_ref(),
+            accounts.approval.as_ref(),
+            &MultisigAction::RevokeMerchant {
+                token_account: accounts.merchant.token_account,
+            },
+        )?;
+
+        let merchant = &accounts.merchant;
+        emit!(MerchantUpdated {
+            market: merchant.market,
+            approver: merchant.approver,
+            token_account: merchant.token_account,
+            approved: false,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Restrict which merchant token accounts a loan can pay (lender only)
+    /// An empty list allows any merchant approved by the admin or the lender.
+    pub fn set_loan_merchants(
+        ctx: Context<SetLoanMerchants>,
+        merchants: Vec<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            merchants.len() <= MAX_LOAN_MERCHANTS,
+            CreditMarketError::TooManyMerchants
+        );
+
+        let loan = &mut ctx.accounts.loan;
+        loan.allowed_merchants = merchants;
+
+        emit!(LoanMerchantsUpdated {
+            loan_id: loan.id,
+            lender: loan.lender,
+            merchants: loan.allowed_merchants.clone(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Accept collateral in any of `collateral_mints`, lending up to `ltv_bps` of its value
+    /// beyond the borrower's tier limit (lender only, while the offer is active)
+    pub fn set_offer_collateral_terms(
+        ctx: Context<SetOfferCollateralTerms>,
+        collateral_mints: Vec<Pubkey>,
+        ltv_bps: u16,
+    ) -> Result<()> {
+        require!(
+            collateral_mints.len() <= MAX_COLLATERAL_MINTS
+                && ltv_bps <= 10000
+                && (ltv_bps > 0 || collateral_mints.is_empty()),
+            CreditMarketError::InvalidCollateralTerms
+        );
+
+        let offer = &mut ctx.accounts.offer;
+        offer.collateral_mints = collateral_mints;
+        offer.ltv_bps = ltv_bps;
+
+        emit!(OfferCollateralTermsUpdated {
+            lender: offer.lender,
+            offer: offer.key(),
+            collateral_mints: offer.collateral_mints.clone(),
+            ltv_bps,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set the health below which anyone can issue a margin call on loans from this offer
+    /// (lender only, while the offer is active)
+    pub fn set_offer_warning_threshold(
+        ctx: Context<SetOfferWarningThreshold>,
+        warning_threshold_bps: u16,
+    ) -> Result<()> {
+        let offer = &mut ctx.accounts.offer;
+        require!(
+            warning_threshold_bps >= offer.liquidation_threshold_bps
+                && warning_threshold_bps <= MAX_WARNING_THRESHOLD_BPS,
+            CreditMarketError::InvalidWarningThreshold
+        );
+        offer.warning_threshold_bps = warning_threshold_bps;
+
+        emit!(OfferWarningThresholdUpdated {
+            lender: offer.lender,
+            offer: offer.key(),
+            warning_threshold_bps,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set what `repay_loan` charges however early loans from this offer are repaid:
+    /// at least `min_interest_secs` of interest, a flat `origination_fee_bps` of principal
+    /// and `prepayment_penalty_bps` of principal before `end_time` (lender only, while the
+    /// offer is active)
+    pub fn set_offer_repayment_terms(
+        ctx: Context<SetOfferRepaymentTerms>,
+        min_interest_secs: u64,
+        origination_fee_bps: u16,
+        prepayment_penalty_bps: u16,
+    ) -> Result<()> {
+        let offer = &mut ctx.accounts.offer;
+        require!(
+            min_interest_secs <= offer.max_duration_secs
+                && origination_fee_bps <= MAX_ORIGINATION_FEE_BPS
+                && prepayment_penalty_bps <= MAX_PREPAYMENT_PENALTY_BPS,
+            CreditMarketError::InvalidRepaymentTerms
+        );
+        offer.min_interest_secs = min_interest_secs;
+        offer.origination_fee_bps = origination_fee_bps;
+        offer.prepayment_penalty_bps = prepayment_penalty_bps;
+
+        emit!(OfferRepaymentTermsUpdated {
+            lender: offer.lender,
+            offer: offer.key(),
+            min_interest_secs,
+            origination_fee_bps,
+            prepayment_penalty_bps,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Allow flash loans of this offer's escrow at `flash_fee_bps` of the amount borrowed
+    /// (lender only, while the offer is active)
+    pub fn set_offer_flash_terms(
+        ctx: Context<SetOfferFlashTerms>,
+        enabled: bool,
+        flash_fee_bps: u16,
+    ) -> Result<()> {
+        require!(flash_fee_bps <= MAX_FLASH_FEE_BPS, CreditMarketError::InvalidFlashTerms);
+
+        let offer = &mut ctx.accounts.offer;
+        offer.flash_loans_enabled = enabled;
+        offer.flash_fee_bps = flash_fee_bps;
+
+        emit!(OfferFlashTermsUpdated {
+            lender: offer.lender,
+            offer: offer.key(),
+            enabled,
+            flash_fee_bps,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Lend `amount` of an offer's escrow for the rest of the transaction. A `flash_repay`
+    /// for the same offer must come later in the transaction, and this has to be called
+    /// directly rather than through CPI so the transaction's instructions can be checked.
+    /// Fails if the lender has raised the fee above `max_fee_bps`.
+    pub fn flash_borrow<'info>(
+        ctx: Context<'_, '_, '_, 'info, FlashBorrow<'info>>,
+        amount: u64,
+        max_fee_bps: u16,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.global_state.is_paused(PAUSE_NEW_LOANS),
+            CreditMarketError::InstructionPaused
+        );
+
+        let offer = &ctx.accounts.offer;
+        require!(offer.flash_loans_enabled, CreditMarketError::FlashLoansDisabled);
+        require!(offer.flash_loan_amount == 0, CreditMarketError::FlashLoanActive);
+        require!(offer.flash_fee_bps <= max_fee_bps, CreditMarketError::OfferTermsChanged);
+        require!(
+            amount > 0 && amount <= offer.amount && amount <= ctx.accounts.escrow_vault.amount,
+            CreditMarketError::InvalidAmount
+        );
+
+        // The matching repay must be a later top-level instruction for this offer, and the
+        // borrow itself must be top-level so the instruction index refers to it
+        require!(
+            get_stack_height() == TRANSACTION_LEVEL_STACK_HEIGHT,
+            CreditMarketError::FlashLoanCpiNotAllowed
+        );
+        let instructions = ctx.accounts.instructions.to_account_info();
+        let current_index = load_current_index_checked(&instructions)? as usize;
+
+        let offer_key = offer.key();
+        let mut repay_found = false;
+        let mut index = current_index + 1;
+        while let Ok(ix) = load_instruction_at_checked(index, &instructions) {
+            if ix.program_id == crate::ID
+                && ix.data.get(..8) == Some(&instruction::FlashRepay::DISCRIMINATOR[..])
+                && ix.accounts.get(1).map(|meta| meta.pubkey) == Some(offer_key)
+            {
+                repay_found = true;
+                break;
+            }
+            index += 1;
+        }
+        require!(repay_found, CreditMarketError::FlashRepayMissing);
+
+        let fee = flash_loan_fee(amount, offer.flash_fee_bps)?;
+        let escrow_seeds = &[
+            b"escrow",
+            offer.mint.as_ref(),
+            offer.lender.as_ref(),
+            &[ctx.bumps.escrow_vault],
+        ];
+        transfer_tokens(
+            ctx.accounts.escrow_vault.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.borrower_usdc,
+            ctx.accounts.escrow_vault.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[&escrow_seeds[..]],
+        )?;
+
+        let offer = &mut ctx.accounts.offer;
+        offer.flash_loan_amount = amount;
+
+        emit!(FlashLoanBorrowed {
+            offer: offer_key,
+            lender: offer.lender,
+            borrower: ctx.accounts.borrower.key(),
+            amount,
+            fee,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Return an outstanding flash loan to the offer's escrow, paying the fee to the lender
+    /// and the insurance pool's share (`insurance_fee_bps` of the fee) to the pool
+    pub fn flash_repay<'info>(ctx: Context<'_, '_, '_, 'info, FlashRepay<'info>>) -> Result<()> {
+        let offer = &ctx.accounts.offer;
+        let amount = offer.flash_loan_amount;
+        require!(amount > 0, CreditMarketError::FlashLoanNotActive);
+
+        let fee = flash_loan_fee(amount, offer.flash_fee_bps)?;
+        let insurance_fee = fee
+            .checked_mul(ctx.accounts.global_state.insurance_fee_bps as u64)
+            .ok_or(CreditMarketError::MathOverflow)?
+            / 10000;
+        let lender_fee = fee - insurance_fee;
+
+        // Repayer covers transfer fees so the escrow is made whole
+        transfer_tokens_exact(
+            ctx.accounts.repayer_usdc.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.escrow_vault,
+            ctx.accounts.repayer.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+        )?;
+        if lender_fee > 0 {
+            transfer_tokens_exact(
+                ctx.accounts.repayer_usdc.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.lender_usdc,
+                ctx.accounts.repayer.to_account_info(),
+                ctx.remaining_accounts,
+                lender_fee,
+            )?;
+        }
+        if insurance_fee > 0 {
+            transfer_tokens_exact(
+                ctx.accounts.repayer_usdc.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.insurance_pool,
+                ctx.accounts.repayer.to_account_info(),
+                ctx.remaining_accounts,
+                insurance_fee,
+            )?;
+
+            let global_state = &mut ctx.accounts.global_state;
+            global_state.total_insurance_collected = global_state.total_insurance_collected
+                .checked_add(insurance_fee)
+                .ok_or(CreditMarketError::MathOverflow)?;
+            global_state.solvency_ratio_bps = calculate_solvency_ratio_bps(
+                global_state.total_insurance_collected,
+                global_state.total_insurance_claimed,
+            )?;
+        }
+
+        let offer = &mut ctx.accounts.offer;
+        offer.flash_loan_amount = 0;
+
+        emit!(FlashLoanRepaid {
+            offer: offer.key(),
+            lender: offer.lender,
+            repayer: ctx.accounts.repayer.key(),
+            amount,
+            lender_fee,
+            insurance_fee,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Choose whether loans from this offer accrue at the fixed `min_rate_bps` or at the
+    /// market's variable rate (lender only, while the offer is active)
+    pub fn set_offer_rate_mode(ctx: Context<SetOfferRateMode>, rate_mode: RateMode) -> Result<()> {
+        let offer = &mut ctx.accounts.offer;
+        offer.rate_mode = rate_mode;
+
+        emit!(OfferRateModeUpdated {
+            lender: offer.lender,
+            offer: offer.key(),
+            rate_mode,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Replace the spend policy `execute_trade` enforces on a loan (lender only)
+    pub fn set_loan_spend_policy(
+        ctx: Context<SetLoanSpendPolicy>,
+        spend_policy: SpendPolicy,
+    ) -> Result<()> {
+        validate_spend_policy(&spend_policy)?;
+
+        let loan = &mut ctx.accounts.loan;
+        loan.spend_policy = spend_policy;
+
+        emit!(LoanSpendPolicyUpdated {
+            loan_id: loan.id,
+            lender: loan.lender,
+            spend_policy: loan.spend_policy.clone(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Pay an approved merchant from the loan vault, e.g. for API, compute or data purchases
+    /// `memo` carries the invoice ID and is only recorded in the `MerchantPayment` event.
+    pub fn pay_merchant<'info>(
+        ctx: Context<'_, '_, '_, 'info, PayMerchant<'info>>,
+        amount: u64,
+        memo: String,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.global_state.is_paused(PAUSE_EXECUTE_TRADE),
+            CreditMarketError::InstructionPaused
+        );
+        require!(amount > 0, CreditMarketError::InvalidAmount);
+        require!(memo.len() <= MAX_PAYMENT_MEMO_LEN, CreditMarketError::MemoTooLong);
+        authorize_borrower(
+            &ctx.accounts.loan,
+            &ctx.accounts.authority.key(),
+            ctx.accounts.session_key.as_mut(),
+            SESSION_PAY_MERCHANT,
+            amount,
+        )?;
+
+        let loan = &ctx.accounts.loan;
+        let merchant = &ctx.accounts.merchant;
+        require!(
+            merchant.approver == ctx.accounts.global_state.admin
+                || merchant.approver == loan.lender,
+            CreditMarketError::MerchantNotApproved
+        );
+        require!(
+            loan.allowed_merchants.is_empty()
+                || loan.allowed_merchants.contains(&merchant.token_account),
+            CreditMarketError::MerchantNotAllowedForLoan
+        );
+
+        let loan_mint = loan.mint;
+        let loan_id_bytes = loan.id.to_le_bytes();
+        let loan_seeds = &[
+            b"loan",
+            loan_mint.as_ref(),
+            loan_id_bytes.as_ref(),
+            &[loan.bump],
+        ];
+        let received = transfer_tokens(
+            ctx.accounts.loan_vault.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.merchant_token,
+            ctx.accounts.loan.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[&loan_seeds[..]],
+        )?;
+
+        let loan = &mut ctx.accounts.loan;
+        loan.total_spent = loan.total_spent
+            .checked_add(amount)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        emit!(MerchantPayment {
+            loan_id: loan.id,
+            borrower: loan.borrower,
+            merchant: ctx.accounts.merchant_token.key(),
+            merchant_owner: ctx.accounts.merchant_token.owner,
+            amount,
+            received,
+            memo,
+            total_spent: loan.total_spent,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Burn credit and return its USDC to the loan vault, the unwrap path for repayment
+    /// `repay_loan` requires all disbursed credit to have come back this way.
+    pub fn unwrap_to_loan<'info>(
+        ctx: Context<'_, '_, '_, 'info, UnwrapToLoan<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(
+            amount > 0 && amount <= ctx.accounts.loan.credit_outstanding,
+            CreditMarketError::InvalidAmount
+        );
+
+        burn_credit(
+            &ctx.accounts.credit_mint,
+            ctx.accounts.borrower_credit.to_account_info(),
+            ctx.accounts.borrower.to_account_info(),
+            amount,
+            &[],
+        )?;
+        let credit_wrapper = &mut ctx.accounts.credit_wrapper;
+        release_credit_reserve(
+            credit_wrapper,
+            &ctx.accounts.mint,
+            ctx.accounts.credit_reserve.to_account_info(),
+            &mut ctx.accounts.loan_vault,
+            ctx.remaining_accounts,
+            amount,
+        )?;
+        credit_wrapper.total_wrapped = credit_wrapper.total_wrapped
+            .checked_sub(amount)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        let loan = &mut ctx.accounts.loan;
+        loan.credit_outstanding = loan.credit_outstanding
+            .checked_sub(amount)
+            .ok_or(CreditMarketError::MathOverflow)?;
+
+        emit!(LoanCreditReturned {
+            loan_id: loan.id,
+            borrower: loan.borrower,
+            amount,
+            credit_outstanding: loan.credit_outstanding,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Calculate simple interest: principal * rate * duration / (365 days * 10000)
+fn calculate_interest(principal: u64, rate_bps: u16, duration_secs: u64) -> Result<u64> {
+    let interest = (principal as u128)
+        .checked_mul(rate_bps as u128)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .checked_mul(duration_secs as u128)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .checked_div(SECONDS_PER_YEAR as u128)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .checked_div(10000)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    
+    Ok(interest as u64)
+}
+
+/// Interest accrued on `loan` by `now`: simple or per-second compound interest at
+/// `rate_bps` for fixed-rate loans, borrow index growth since the loan started for
+/// variable-rate ones
+fn loan_interest(loan: &Loan, global_state: &GlobalState, now: i64) -> Result<u64> {
+    match loan.rate_mode {
+        RateMode::Fixed => {
+            let duration_secs = now
+                .checked_sub(loan.start_time)
+                .ok_or(CreditMarketError::MathOverflow)? as u64;
+            calculate_interest(loan.principal, loan.rate_bps, duration_secs)
+        }
+        RateMode::Compound => {
+            let duration_secs = now
+                .checked_sub(loan.start_time)
+                .ok_or(CreditMarketError::MathOverflow)? as u64;
+            calculate_compound_interest(loan.principal, loan.rate_bps, duration_secs)
+        }
+        RateMode::Variable => {
+            let index = if loan.exit_borrow_index > 0 {
+                loan.exit_borrow_index
+            } else {
+                borrow_index_at(global_state, now)?
+            };
+            // Round up so truncation never favors the borrower
+            let owed = (loan.principal as u128)
+                .checked_mul(index)
+                .ok_or(CreditMarketError::MathOverflow)?
+                .div_ceil(loan.entry_borrow_index.max(1));
+            u64::try_from(owed.saturating_sub(loan.principal as u128))
+                .map_err(|_| error!(CreditMarketError::MathOverflow))
+        }
+    }
+}
+
+/// Interest on `principal` at `rate_bps` a year compounded every second:
+/// principal * ((1 + rate / SECONDS_PER_YEAR)^duration_secs - 1).
+/// Every step rounds up, so any loan open for a second or more pays some interest.
+fn calculate_compound_interest(principal: u64, rate_bps: u16, duration_secs: u64) -> Result<u64> {
+    if principal == 0 || rate_bps == 0 || duration_secs == 0 {
+        return Ok(0);
+    }
+
+    let rate_per_sec = (rate_bps as u128 * WAD).div_ceil(SECONDS_PER_YEAR as u128 * 10000);
+    let growth = wad_pow_up(WAD + rate_per_sec, duration_secs)?;
+    let owed = wad_mul_up(principal as u128, growth)?;
+    u64::try_from(owed - principal as u128).map_err(|_| error!(CreditMarketError::MathOverflow))
+}
+
+/// Utilization of the market's lender capital in bps: lent principal over lent principal
+/// plus liquidity escrowed in active offers
+fn utilization_bps(global_state: &GlobalState) -> u64 {
+    let borrowed = global_state.total_borrowed as u128;
+    let supplied = borrowed + global_state.total_offered as u128;
+    if supplied == 0 {
+        return 0;
+    }
+    (borrowed * 10000 / supplied) as u64
+}
+
+/// Annual variable rate in bps: base plus slope1 up to optimal utilization, plus slope2
+/// from optimal to full utilization
+fn variable_rate_bps(model: &RateModel, utilization_bps: u64) -> u64 {
+    let base = model.base_rate_bps as u64;
+    let slope1 = model.slope1_bps as u64;
+    let slope2 = model.slope2_bps as u64;
+    let optimal = model.optimal_utilization_bps as u64;
+
+    if utilization_bps <= optimal {
+        base + slope1 * utilization_bps / optimal
+    } else {
+        base + slope1 + slope2 * (utilization_bps - optimal) / (10000 - optimal)
+    }
+}
+
+/// Borrow index projected to `now` at the current variable rate, rounded up
+fn borrow_index_at(global_state: &GlobalState, now: i64) -> Result<u128> {
+    let elapsed = now.saturating_sub(global_state.index_updated_at).max(0) as u128;
+    let rate_bps = variable_rate_bps(&global_state.rate_model, utilization_bps(global_state)) as u128;
+    if elapsed == 0 || rate_bps == 0 {
+        return Ok(global_state.borrow_index);
+    }
+
+    let growth = global_state.borrow_index
+        .checked_mul(rate_bps)
+        .and_then(|v| v.checked_mul(elapsed))
+        .ok_or(CreditMarketError::MathOverflow)?
+        .div_ceil(SECONDS_PER_YEAR as u128 * 10000);
+    global_state.borrow_index
+        .checked_add(growth)
+        .ok_or(error!(CreditMarketError::MathOverflow))
+}
+
+/// Bring the market's borrow index up to `now`. Call before utilization or the rate
+/// model changes, so elapsed time accrues at the rate that applied to it.
+fn accrue_borrow_index(global_state: &mut GlobalState, now: i64) -> Result<()> {
+    global_state.borrow_index = borrow_index_at(global_state, now)?;
+    global_state.index_updated_at = global_state.index_updated_at.max(now);
+    Ok(())
+}
+
+/// Take a closing loan's principal out of utilization and freeze its variable-rate
+/// interest at the current borrow index
+fn close_loan_borrow(loan: &mut Loan, global_state: &mut GlobalState, now: i64) -> Result<()> {
+    accrue_borrow_index(global_state, now)?;
+    loan.exit_borrow_index = global_state.borrow_index;
+    global_state.total_borrowed = global_state.total_borrowed.saturating_sub(loan.principal);
+    Ok(())
+}
+
+/// Flash loan fee, rounded up
+fn flash_loan_fee(amount: u64, flash_fee_bps: u16) -> Result<u64> {
+    let fee = (amount as u128)
+        .checked_mul(flash_fee_bps as u128)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .div_ceil(10000);
+    u64::try_from(fee).map_err(|_| error!(CreditMarketError::MathOverflow))
+}
+
+/// Solvency ratio of the insurance pool: collected / claimed in bps, capped at 10x
+fn calculate_solvency_ratio_bps(collected: u64, claimed: u64) -> Result<u32> {
+    if claimed == 0 {
+        return Ok(MAX_SOLVENCY_RATIO_BPS);
+    }
+    let ratio = (collected as u128)
+        .checked_mul(10000)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .checked_div(claimed as u128)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    Ok(ratio.min(MAX_SOLVENCY_RATIO_BPS as u128) as u32)
+}
+
+/// Insurance premium in bps of interest for a borrower of the given credit tier:
+/// base * target_solvency / solvency, clamped to [min, max], then discounted per tier above 1
+fn calculate_insurance_premium_bps(global_state: &GlobalState, credit_tier: u8) -> Result<u16> {
+    let solvency = global_state.solvency_ratio_bps.max(1) as u128;
+    let scaled = (global_state.insurance_fee_bps as u128)
+        .checked_mul(global_state.target_solvency_bps as u128)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .checked_div(solvency)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    let clamped = scaled.clamp(
+        global_state.min_premium_bps as u128,
+        global_state.max_premium_bps as u128,
+    );
+
+    let discount_bps = (global_state.tier_discount_bps as u128)
+        .checked_mul(credit_tier.saturating_sub(1) as u128)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .min(10000);
+    let premium = clamped
+        .checked_mul(10000 - discount_bps)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .checked_div(10000)
+        .ok_or(CreditMarketError::MathOverflow)?;
+
+    Ok(premium as u16)
+}
+
+/// Check a governance action's parameters before it is queued or applied
+fn validate_governance_action(action: &GovernanceAction) -> Result<()> {
+    match action {
+        GovernanceAction::UpdateFeeRates { insurance_fee_bps, protocol_fee_bps } => {
+            if let Some(insurance_bps) = insurance_fee_bps {
+                require!(*insurance_bps <= 2000, CreditMarketError::InvalidFeeRate); // Max 20%
+            }
+            if let Some(protocol_bps) = protocol_fee_bps {
+                require!(*protocol_bps <= 500, CreditMarketError::InvalidFeeRate); // Max 5%
+            }
+        }
+        GovernanceAction::AddWhitelistedProgram { .. }
+        | GovernanceAction::RemoveWhitelistedProgram { .. }
+        | GovernanceAction::RemovePriceFeed { .. } => {}
+        GovernanceAction::SetPriceFeed { feed } => {
+            require!(
+                feed.max_staleness_secs > 0 && feed.max_confidence_bps <= 10000,
+                CreditMarketError::InvalidPriceFeed
+            );
+        }
+        GovernanceAction::SetPauseAuthority { .. } => {}
+        GovernanceAction::ConfigureCircuitBreaker { window_secs, max_defaults } => {
+            require!(*window_secs >= 0, CreditMarketError::InvalidDuration);
+            require!(
+                *max_defaults as usize <= MAX_BREAKER_DEFAULTS,
+                CreditMarketError::InvalidCircuitBreaker
+            );
+        }
+        GovernanceAction::UpdatePremiumCurve {
+            min_premium_bps,
+            max_premium_bps,
+            target_solvency_bps,
+            tier_discount_bps,
+        } => {
+            if let Some(max_bps) = max_premium_bps {
+                require!(*max_bps <= 2000, CreditMarketError::InvalidFeeRate); // Max 20%
+            }
+            if let (Some(min_bps), Some(max_bps)) = (min_premium_bps, max_premium_bps) {
+                require!(min_bps <= max_bps, CreditMarketError::InvalidPremiumCurve);
+            }
+            if let Some(target_bps) = target_solvency_bps {
+                require!(
+                    *target_bps > 0 && *target_bps <= MAX_SOLVENCY_RATIO_BPS,
+                    CreditMarketError::InvalidPremiumCurve
+                );
+            }
+            if let Some(discount_bps) = tier_discount_bps {
+                // Tier 5 gets 4x the discount, which must stay below 100%
+                require!(*discount_bps <= 2500, CreditMarketError::InvalidPremiumCurve);
+            }
+        }
+        GovernanceAction::SetRateModel { model } => {
+            require!(
+                model.optimal_utilization_bps > 0
+                    && model.optimal_utilization_bps <= 10000
+                    && model.base_rate_bps as u32 + model.slope1_bps as u32 + model.slope2_bps as u32
+                        <= MAX_VARIABLE_RATE_BPS,
+                CreditMarketError::InvalidRateModel
+            );
+        }
+        GovernanceAction::SetTimelockDelay { delay_secs } => {
+            require!(
+                *delay_secs >= 0 && *delay_secs <= MAX_TIMELOCK_DELAY_SECS,
+                CreditMarketError::InvalidTimelockDelay
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Apply a governance action to the global state, emitting the matching event
+fn apply_governance_action(
+    global_state: &mut GlobalState,
+    action: &GovernanceAction,
+    timestamp: i64,
+) -> Result<()> {
+    match action {
+        GovernanceAction::UpdateFeeRates { insurance_fee_bps, protocol_fee_bps } => {
+            if let Some(insurance_bps) = insurance_fee_bps {
+                global_state.insurance_fee_bps = *insurance_bps;
+            }
+            if let Some(protocol_bps) = protocol_fee_bps {
+                global_state.fee_bps = *protocol_bps;
+            }
+
+            emit!(FeeRatesUpdated {
+                insurance_fee_bps: global_state.insurance_fee_bps,
+                protocol_fee_bps: global_state.fee_bps,
+                timestamp,
+            });
+
+            // The insurance fee is the base of the premium curve
+            if insurance_fee_bps.is_some() {
+                emit_premium_curve_updated(global_state, timestamp);
+            }
+        }
+        GovernanceAction::AddWhitelistedProgram { program_id } => {
+            if !global_state.whitelisted_programs.contains(program_id) {
+                require!(
+                    global_state.whitelisted_programs.len() < MAX_WHITELISTED_PROGRAMS,
+                    CreditMarketError::WhitelistFull
+                );
+                global_state.whitelisted_programs.push(*program_id);
+            }
+        }
+        GovernanceAction::RemoveWhitelistedProgram { program_id } => {
+            global_state.whitelisted_programs.retain(|p| p != program_id);
+        }
+        GovernanceAction::SetPriceFeed { feed } => {
+            match global_state.price_feeds.iter_mut().find(|f| f.mint == feed.mint) {
+                Some(existing) => *existing = feed.clone(),
+                None => {
+                    require!(
+                        global_state.price_feeds.len() < MAX_PRICE_FEEDS,
+                        CreditMarketError::PriceFeedsFull
+                    );
+                    global_state.price_feeds.push(feed.clone());
+                }
+            }
+
+            emit!(PriceFeedUpdated {
+                mint: feed.mint,
+                oracle_program: feed.oracle_program,
+                feed: feed.feed,
+                removed: false,
+                timestamp,
+            });
+        }
+        GovernanceAction::RemovePriceFeed { mint } => {
+            global_state.price_feeds.retain(|f| f.mint != *mint);
+
+            emit!(PriceFeedUpdated {
+                mint: *mint,
+                oracle_program: Pubkey::default(),
+                feed: Pubkey::default(),
+                removed: true,
+                timestamp,
+            });
+        }
+        GovernanceAction::SetPauseAuthority { pause_authority } => {
+            global_state.pause_authority = *pause_authority;
+
+            emit!(PauseAuthorityUpdated {
+                pause_authority: *pause_authority,
+                timestamp,
+            });
+        }
+        GovernanceAction::ConfigureCircuitBreaker { window_secs, max_defaults } => {
+            global_state.breaker_window_secs = *window_secs;
+            global_state.breaker_max_defaults = *max_defaults;
+            global_state.breaker_recent_defaults.clear();
+
+            emit!(CircuitBreakerConfigured {
+                window_secs: *window_secs,
+                max_defaults: *max_defaults,
+                timestamp,
+            });
+        }
+        GovernanceAction::UpdatePremiumCurve {
+            min_premium_bps,
+            max_premium_bps,
+            target_solvency_bps,
+            tier_discount_bps,
+        } => {
+            // Bounds against the current curve are checked when the action lands
+            let min_bps = min_premium_bps.unwrap_or(global_state.min_premium_bps);
+            let max_bps = max_premium_bps.unwrap_or(global_state.max_premium_bps);
+            require!(min_bps <= max_bps, CreditMarketError::InvalidPremiumCurve);
+
+            global_state.min_premium_bps = min_bps;
+            global_state.max_premium_bps = max_bps;
+            if let Some(target_bps) = target_solvency_bps {
+                global_state.target_solvency_bps = *target_bps;
+            }
+            if let Some(discount_bps) = tier_discount_bps {
+                global_state.tier_discount_bps = *discount_bps;
+            }
+
+            emit_premium_curve_updated(global_state, timestamp);
+        }
+        GovernanceAction::SetRateModel { model } => {
+            // Time so far accrues at the old rate
+            accrue_borrow_index(global_state, timestamp)?;
+            global_state.rate_model = model.clone();
+
+            emit!(RateModelUpdated {
+                base_rate_bps: model.base_rate_bps,
+                slope1_bps: model.slope1_bps,
+                slope2_bps: model.slope2_bps,
+                optimal_utilization_bps: model.optimal_utilization_bps,
+                timestamp,
+            });
+        }
+        GovernanceAction::SetTimelockDelay { delay_secs } => {
+            global_state.timelock_delay_secs = *delay_secs;
+
+            emit!(TimelockDelayUpdated {
+                delay_secs: *delay_secs,
+                timestamp,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check a multisig signer set: non-empty, no duplicates, 1 <= threshold <= signers
+fn validate_multisig_signers(signers: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(
+        !signers.is_empty() && signers.len() <= MAX_MULTISIG_SIGNERS,
+        CreditMarketError::InvalidMultisig
+    );
+    require!(
+        threshold > 0 && threshold as usize <= signers.len(),
+        CreditMarketError::InvalidMultisig
+    );
+    for (i, signer) in signers.iter().enumerate() {
+        require!(
+            !signers[i + 1..].contains(signer),
+            CreditMarketError::InvalidMultisig
+        );
+    }
+    Ok(())
+}
+
+/// Delay between a multisig proposal reaching threshold and becoming executable.
+/// Fee and whitelist changes wait out the governance timelock like any queued action.
+fn multisig_action_delay(global_state: &GlobalState, action: &MultisigAction) -> i64 {
+    match action {
+        MultisigAction::Governance(_) | MultisigAction::ApproveMerchant { .. } => {
+            global_state.timelock_delay_secs
+        }
+        _ => 0,
+    }
+}
+
+/// Let `authority` act as admin: either it is the admin, or the admin is `multisig` and
+/// `approval` is its executable proposal for `action`, made by `authority`.
+/// The approval is closed back to the proposer so it can only be used once.
+fn authorize_admin<'info>(
+    global_state: &Account<'info, GlobalState>,
+    authority: &Signer<'info>,
+    multisig: Option<&Account<'info, Multisig>>,
+    approval: Option<&Account<'info, MultisigProposal>>,
+    action: &MultisigAction,
+) -> Result<()> {
+    if global_state.admin == authority.key() {
+        return Ok(());
+    }
+
+    let (multisig, approval) = multisig
+        .zip(approval)
+        .ok_or(CreditMarketError::Unauthorized)?;
+    require!(global_state.admin == multisig.key(), CreditMarketError::Unauthorized);
+    require!(
+        approval.multisig == multisig.key() && approval.proposer == authority.key(),
+        CreditMarketError::Unauthorized
+    );
+    require!(approval.market == global_state.key(), CreditMarketError::MarketMismatch);
+    require!(
+        approval.owner_set_seqno == multisig.owner_set_seqno,
+        CreditMarketError::StaleProposal
+    );
+    require!(approval.eta != 0, CreditMarketError::ThresholdNotMet);
+    require!(
+        Clock::get()?.unix_timestamp >= approval.eta,
+        CreditMarketError::TimelockNotElapsed
+    );
+    require!(approval.action == *action, CreditMarketError::ApprovalActionMismatch);
+
+    emit!(MultisigActionExecuted {
+        multisig: multisig.key(),
+        proposal_id: approval.id,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    approval.close(authority.to_account_info())
+}
+
+/// Count a default or liquidation towards the circuit breaker, tripping it if more than
+/// `breaker_max_defaults` happened within the last `breaker_window_secs`. Only the latest
+/// `breaker_max_defaults + 1` default times are kept, which is all the check needs.
+fn record_default_for_breaker(global_state: &mut GlobalState, now: i64) -> Result<()> {
+    if global_state.breaker_window_secs == 0 {
+        return Ok(());
+    }
+
+    let history_len = (global_state.breaker_max_defaults as usize + 1).min(BREAKER_HISTORY_LEN);
+    let recent = &mut global_state.breaker_recent_defaults;
+    recent.push(now);
+    if recent.len() > history_len {
+        let excess = recent.len() - history_len;
+        recent.drain(..excess);
+    }
+
+    let window_start = now.saturating_sub(global_state.breaker_window_secs);
+    let defaults_in_window = recent.iter().filter(|&&t| t > window_start).count() as u32;
+
+    if defaults_in_window > global_state.breaker_max_defaults
+        && global_state.paused_flags & PAUSE_ALL != PAUSE_ALL
+    {
+        global_state.paused_flags |= PAUSE_ALL;
+
+        emit!(CircuitBreakerTripped {
+            defaults_in_window,
+            window_start,
+            paused_flags: global_state.paused_flags,
+            timestamp: now,
+        });
+    }
+
+    Ok(())
+}
+
+fn emit_premium_curve_updated(global_state: &GlobalState, timestamp: i64) {
+    emit!(PremiumCurveUpdated {
+        base_premium_bps: global_state.insurance_fee_bps,
+        min_premium_bps: global_state.min_premium_bps,
+        max_premium_bps: global_state.max_premium_bps,
+        target_solvency_bps: global_state.target_solvency_bps,
+        tier_discount_bps: global_state.tier_discount_bps,
+        solvency_ratio_bps: global_state.solvency_ratio_bps,
+        timestamp,
+    });
+}
+
+/// Transfer with `transfer_checked`, forwarding the accounts the mint's transfer hook needs
+/// from `remaining_accounts`. Returns the amount `to` actually received, which is less than
+/// `amount` for mints with a transfer fee.
+fn transfer_tokens<'info>(
+    from: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &mut InterfaceAccount<'info, TokenAccount>,
+    authority: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    let token_program_id = *mint_info.owner;
+    let balance_before = to.amount;
+
+    invoke_transfer_checked(
+        &token_program_id,
+        from,
+        mint_info,
+        to.to_account_info(),
+        authority,
+        remaining_accounts,
+        amount,
+        mint.decimals,
+        signer_seeds,
+    )?;
+
+    to.reload()?;
+    Ok(to.amount
+        .checked_sub(balance_before)
+        .ok_or(CreditMarketError::MathOverflow)?)
+}
+
+/// Transfer so that `to` receives exactly `net_amount`, with the sender covering any
+/// transfer fee. Fails if the recipient ends up with less.
+fn transfer_tokens_exact<'info>(
+    from: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &mut InterfaceAccount<'info, TokenAccount>,
+    authority: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    net_amount: u64,
+) -> Result<()> {
+    let gross_amount = amount_with_transfer_fee(mint, net_amount)?;
+    let received = transfer_tokens(from, mint, to, authority, remaining_accounts, gross_amount, &[])?;
+    require!(received >= net_amount, CreditMarketError::TransferAmountMismatch);
+    Ok(())
+}
+
+/// Amount to send so the recipient nets `net_amount` after the mint's transfer fee
+fn amount_with_transfer_fee(mint: &InterfaceAccount<Mint>, net_amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+
+    match mint_state.get_extension::<TransferFeeConfig>() {
+        Ok(fee_config) => {
+            let fee = fee_config
+                .calculate_inverse_epoch_fee(Clock::get()?.epoch, net_amount)
+                .ok_or(CreditMarketError::MathOverflow)?;
+            Ok(net_amount
+                .checked_add(fee)
+                .ok_or(CreditMarketError::MathOverflow)?)
+        }
+        Err(_) => Ok(net_amount),
+    }
+}
+
+/// Check that `authority` may act for the loan's borrower: either the borrower itself or
+/// an unexpired session key allowed `action` whose spend cap still covers `spend`
+fn authorize_borrower(
+    loan: &Loan,
+    authority: &Pubkey,
+    session_key: Option<&mut Account<SessionKey>>,
+    action: u8,
+    spend: u64,
+) -> Result<()> {
+    if *authority == loan.borrower {
+        return Ok(());
+    }
+
+    let session_key = session_key.ok_or(CreditMarketError::Unauthorized)?;
+    require!(
+        session_key.borrower == loan.borrower && session_key.key == *authority,
+        CreditMarketError::InvalidSessionKey
+    );
+    require!(
+        Clock::get()?.unix_timestamp < session_key.expires_at,
+        CreditMarketError::SessionKeyExpired
+    );
+    require!(
+        session_key.allowed_actions & action != 0,
+        CreditMarketError::SessionActionNotAllowed
+    );
+
+    let spent = session_key.spent
+        .checked_add(spend)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    require!(spent <= session_key.spend_cap, CreditMarketError::SessionSpendCapExceeded);
+    session_key.spent = spent;
+    Ok(())
+}
+
+fn validate_spend_policy(policy: &SpendPolicy) -> Result<()> {
+    require!(
+        policy.max_vault_fraction_bps <= 10000
+            && policy.allowed_programs.len() <= MAX_POLICY_PROGRAMS,
+        CreditMarketError::InvalidSpendPolicy
+    );
+    Ok(())
+}
+
+/// Check a trade against the loan's spend policy and count it towards the daily limit.
+/// Zero limits and an empty program list are unrestricted.
+fn enforce_spend_policy(
+    loan: &mut Loan,
+    vault_balance: u64,
+    target_program: &Pubkey,
+    notional: u64,
+    now: i64,
+) -> Result<()> {
+    let policy = &loan.spend_policy;
+    require!(
+        policy.allowed_programs.is_empty() || policy.allowed_programs.contains(target_program),
+        CreditMarketError::ProgramNotAllowedByPolicy
+    );
+    require!(
+        policy.max_notional_per_trade == 0 || notional <= policy.max_notional_per_trade,
+        CreditMarketError::TradeNotionalExceeded
+    );
+    if policy.max_vault_fraction_bps > 0 {
+        let max_notional = (vault_balance as u128)
+            .checked_mul(policy.max_vault_fraction_bps as u128)
+            .ok_or(CreditMarketError::MathOverflow)?
+            .checked_div(10000)
+            .ok_or(CreditMarketError::MathOverflow)?;
+        require!(
+            (notional as u128) <= max_notional,
+            CreditMarketError::TradeExceedsVaultFraction
+        );
+    }
+
+    let day_end = loan.trade_day_start
+        .checked_add(TRADE_DAY_SECS)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    if now >= day_end {
+        loan.trade_day_start = now;
+        loan.trades_today = 0;
+    }
+    loan.trades_today = loan.trades_today
+        .checked_add(1)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    require!(
+        policy.max_trades_per_day == 0 || loan.trades_today <= policy.max_trades_per_day,
+        CreditMarketError::DailyTradeLimitExceeded
+    );
+    Ok(())
+}
+
+/// Split `remaining_accounts` into the (token account, price feed) pairs for the loan's
+/// positions and whatever follows them
+fn split_position_accounts<'a, 'info>(
+    loan: &Loan,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    let position_account_count = loan.positions.len() * 2;
+    require!(
+        remaining_accounts.len() >= position_account_count,
+        CreditMarketError::MissingPositionAccounts
+    );
+    Ok(remaining_accounts.split_at(position_account_count))
+}
+
+/// Portfolio value of a loan against what the borrower owes now
+struct LoanHealth {
+    vault_balance: u64,
+    credit_held: u64,
+    position_values: Vec<u64>,
+    positions_value: u64,
+    portfolio_value: u64,
+    expected_repayment: u64,
+    health_factor_bps: u16,
+}
+
+/// Health factor = portfolio value * 10000 / expected repayment, where the portfolio is
+/// the vault, credit the borrower still holds (liquidation claws it back) and
+/// oracle-priced positions
+fn loan_health(
+    loan: &Account<Loan>,
+    loan_vault: &InterfaceAccount<TokenAccount>,
+    borrower_credit: Option<&InterfaceAccount<TokenAccount>>,
+    global_state: &GlobalState,
+    position_accounts: &[AccountInfo],
+    quote_decimals: u8,
+    now: i64,
+) -> Result<LoanHealth> {
+    let vault_balance = loan_vault.amount;
+    let credit_held = loan_credit_held(loan, borrower_credit)?;
+    let position_values = value_loan_positions(
+        loan,
+        &global_state.price_feeds,
+        position_accounts,
+        quote_decimals,
+        now,
+    )?;
+    let positions_value = position_values
+        .iter()
+        .try_fold(0u64, |total, value| total.checked_add(*value))
+        .ok_or(CreditMarketError::MathOverflow)?;
+    let portfolio_value = vault_balance
+        .checked_add(credit_held)
+        .and_then(|v| v.checked_add(positions_value))
+        .ok_or(CreditMarketError::MathOverflow)?;
+
+    let interest = loan_interest(loan, global_state, now)?;
+    let expected_repayment = loan.principal
+        .checked_add(interest)
+        .ok_or(CreditMarketError::MathOverflow)?;
+
+    let health_factor_bps = if expected_repayment > 0 {
+        (portfolio_value as u128)
+            .checked_mul(10000)
+            .ok_or(CreditMarketError::MathOverflow)?
+            .checked_div(expected_repayment as u128)
+            .ok_or(CreditMarketError::MathOverflow)?
+            .min(u16::MAX as u128) as u16
+    } else {
+        10000
+    };
+
+    Ok(LoanHealth {
+        vault_balance,
+        credit_held,
+        position_values,
+        positions_value,
+        portfolio_value,
+        expected_repayment,
+        health_factor_bps,
+    })
+}
+
+/// Value of each of the loan's positions in market-mint units. `accounts` holds a
+/// (token account, price feed) pair per position, in `loan.positions` order.
+fn value_loan_positions(
+    loan: &Account<Loan>,
+    price_feeds: &[PriceFeed],
+    accounts: &[AccountInfo],
+    quote_decimals: u8,
+    now: i64,
+) -> Result<Vec<u64>> {
+    let mut values = Vec::with_capacity(loan.positions.len());
+    for (position, pair) in loan.positions.iter().zip(accounts.chunks_exact(2)) {
+        let (token_info, feed_info) = (&pair[0], &pair[1]);
+        require!(
+            token_info.key() == position.token_account
+                && (*token_info.owner == anchor_spl::token::ID
+                    || *token_info.owner == anchor_spl::token_2022::ID),
+            CreditMarketError::InvalidPosition
+        );
+        let token_account = TokenAccount::try_deserialize(&mut &token_info.try_borrow_data()?[..])?;
+        require!(token_account.owner == loan.key(), CreditMarketError::InvalidPosition);
+        if token_account.amount == 0 {
+            values.push(0);
+            continue;
+        }
+
+        let value = oracle_value(
+            price_feeds,
+            &position.mint,
+            feed_info,
+            token_account.amount,
+            position.decimals,
+            quote_decimals,
+            now,
+        )?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Auction price as a fraction of the oracle price, decaying linearly from
+/// `AUCTION_START_PRICE_BPS` to `AUCTION_FLOOR_PRICE_BPS` over `AUCTION_DURATION_SECS`
+fn auction_price_bps(auction_start: i64, now: i64) -> Result<u64> {
+    let elapsed = now
+        .checked_sub(auction_start)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .clamp(0, AUCTION_DURATION_SECS) as u64;
+    let decay = (AUCTION_START_PRICE_BPS - AUCTION_FLOOR_PRICE_BPS)
+        .checked_mul(elapsed)
+        .ok_or(CreditMarketError::MathOverflow)?
+        / AUCTION_DURATION_SECS as u64;
+    Ok(AUCTION_START_PRICE_BPS - decay)
+}
+
+/// Value `amount` of `mint` in market-mint units using the market's price feed for `mint`
+fn oracle_value(
+    price_feeds: &[PriceFeed],
+    mint: &Pubkey,
+    feed_info: &AccountInfo,
+    amount: u64,
+    token_decimals: u8,
+    quote_decimals: u8,
+    now: i64,
+) -> Result<u64> {
+    let feed = price_feeds
+        .iter()
+        .find(|f| f.mint == *mint)
+        .ok_or(CreditMarketError::PriceFeedNotConfigured)?;
+    require!(
+        feed_info.key() == feed.feed && *feed_info.owner == feed.oracle_program,
+        CreditMarketError::InvalidPriceFeed
+    );
+    let oracle_price = OraclePrice::load(feed_info)?;
+    let price = oracle_price.conservative_price(feed, now)?;
+
+    value_in_quote(amount, price, oracle_price.expo, token_decimals, quote_decimals)
+}
+
+/// Empty the loan's collateral vault into `to`, signed by the loan. `seized` marks a
+/// transfer to the lender on liquidation or default rather than a release to the borrower.
+fn release_collateral<'info>(
+    loan: &mut Account<'info, Loan>,
+    collateral_vault: Option<&InterfaceAccount<'info, TokenAccount>>,
+    collateral_mint: Option<&InterfaceAccount<'info, Mint>>,
+    to: Option<&mut InterfaceAccount<'info, TokenAccount>>,
+    remaining_accounts: &[AccountInfo<'info>],
+    seized: bool,
+) -> Result<u64> {
+    if loan.collateral_amount == 0 {
+        return Ok(0);
+    }
+
+    let collateral_vault = collateral_vault.ok_or(CreditMarketError::CollateralAccountsMissing)?;
+    let collateral_mint = collateral_mint.ok_or(CreditMarketError::CollateralAccountsMissing)?;
+    let to = to.ok_or(CreditMarketError::CollateralAccountsMissing)?;
+    require!(
+        collateral_vault.key() == loan.collateral_vault,
+        CreditMarketError::InvalidCollateralVault
+    );
+    require!(
+        collateral_mint.key() == loan.collateral_mint && to.mint == loan.collateral_mint,
+        CreditMarketError::MintMismatch
+    );
+
+    let amount = collateral_vault.amount;
+    if amount > 0 {
+        let loan_id_bytes = loan.id.to_le_bytes();
+        let loan_seeds = &[
+            b"loan",
+            loan.mint.as_ref(),
+            loan_id_bytes.as_ref(),
+            &[loan.bump],
+        ];
+        transfer_tokens(
+            collateral_vault.to_account_info(),
+            collateral_mint,
+            to,
+            loan.to_account_info(),
+            remaining_accounts,
+            amount,
+            &[&loan_seeds[..]],
+        )?;
+    }
+
+    let collateral_vault_key = collateral_vault.key();
+    loan.positions.retain(|p| p.token_account != collateral_vault_key);
+    loan.collateral_amount = 0;
+
+    emit!(CollateralReleased {
+        loan_id: loan.id,
+        mint: loan.collateral_mint,
+        recipient: to.owner,
+        amount,
+        seized,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(amount)
+}
+
+/// Convert `amount` base units of a token priced at `price * 10^expo` quote per whole
+/// token into quote base units
+fn value_in_quote(
+    amount: u64,
+    price: u64,
+    expo: i32,
+    token_decimals: u8,
+    quote_decimals: u8,
+) -> Result<u64> {
+    let scale = expo
+        .checked_add(quote_decimals as i32)
+        .and_then(|e| e.checked_sub(token_decimals as i32))
+        .ok_or(CreditMarketError::MathOverflow)?;
+    let factor = 10u128
+        .checked_pow(scale.unsigned_abs())
+        .ok_or(CreditMarketError::MathOverflow)?;
+    let raw = (amount as u128)
+        .checked_mul(price as u128)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    let value = if scale >= 0 {
+        raw.checked_mul(factor)
+    } else {
+        raw.checked_div(factor)
+    }
+    .ok_or(CreditMarketError::MathOverflow)?;
+
+    u64::try_from(value).map_err(|_| error!(CreditMarketError::MathOverflow))
+}
+
+/// Rescale a reputation tier limit from `TIER_LIMIT_DECIMALS` to the market mint's decimals
+fn scale_tier_limit(limit: u64, decimals: u8) -> Result<u64> {
+    let scale = decimals as i32 - TIER_LIMIT_DECIMALS as i32;
+    let factor = 10u64
+        .checked_pow(scale.unsigned_abs())
+        .ok_or(CreditMarketError::MathOverflow)?;
+    if scale >= 0 {
+        limit.checked_mul(factor).ok_or(error!(CreditMarketError::MathOverflow))
+    } else {
+        Ok(limit / factor)
+    }
+}
+
+/// Mint credit to `to`, signed by the wrapper as mint authority
+fn mint_credit<'info>(
+    credit_wrapper: &Account<'info, CreditWrapper>,
+    credit_mint: &InterfaceAccount<'info, Mint>,
+    to: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let mint_info = credit_mint.to_account_info();
+    let wrapper_seeds = &[
+        b"credit_wrapper",
+        credit_wrapper.market.as_ref(),
+        &[credit_wrapper.bump],
+    ];
+
+    invoke_signed(
+        &spl_token_2022::instruction::mint_to(
+            mint_info.owner,
+            mint_info.key,
+            to.key,
+            &credit_wrapper.key(),
+            &[],
+            amount,
+        )?,
+        &[mint_info.clone(), to, credit_wrapper.to_account_info()],
+        &[&wrapper_seeds[..]],
+    )?;
+    Ok(())
+}
+
+/// Burn credit from `from`. `authority` is either the holder or, for clawbacks, the
+/// wrapper signing as permanent delegate.
+fn burn_credit<'info>(
+    credit_mint: &InterfaceAccount<'info, Mint>,
+    from: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let mint_info = credit_mint.to_account_info();
+
+    invoke_signed(
+        &spl_token_2022::instruction::burn(
+            mint_info.owner,
+            from.key,
+            mint_info.key,
+            authority.key,
+            &[],
+            amount,
+        )?,
+        &[from, mint_info.clone(), authority],
+        signer_seeds,
+    )?;
+    Ok(())
+}
+
+/// Pay `amount` of backing USDC out of the credit reserve, signed by the wrapper
+fn release_credit_reserve<'info>(
+    credit_wrapper: &Account<'info, CreditWrapper>,
+    mint: &InterfaceAccount<'info, Mint>,
+    credit_reserve: AccountInfo<'info>,
+    to: &mut InterfaceAccount<'info, TokenAccount>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<u64> {
+    let wrapper_seeds = &[
+        b"credit_wrapper",
+        credit_wrapper.market.as_ref(),
+        &[credit_wrapper.bump],
+    ];
+
+    transfer_tokens(
+        credit_reserve,
+        mint,
+        to,
+        credit_wrapper.to_account_info(),
+        remaining_accounts,
+        amount,
+        &[&wrapper_seeds[..]],
+    )
+}
+
+/// Credit the borrower still holds in the loan's credit account, capped at what was disbursed
+fn loan_credit_held(
+    loan: &Loan,
+    borrower_credit: Option<&InterfaceAccount<TokenAccount>>,
+) -> Result<u64> {
+    if loan.credit_outstanding == 0 {
+        return Ok(0);
+    }
+
+    let borrower_credit = borrower_credit.ok_or(CreditMarketError::CreditAccountsMissing)?;
+    require!(
+        borrower_credit.key() == loan.credit_account,
+        CreditMarketError::CreditAccountMismatch
+    );
+    Ok(borrower_credit.amount.min(loan.credit_outstanding))
+}
+
+/// Repayment of `loan` at `now` as `repay_loan` charges it: interest (at least
+/// `min_interest_secs` worth), the origination fee and any prepayment penalty, split
+/// between the lender, the insurance pool (dynamic premium) and the protocol treasury
+fn calculate_repayment(
+    loan: &Loan,
+    global_state: &GlobalState,
+    borrower_profile: &BorrowerProfile,
+    mint: &InterfaceAccount<Mint>,
+    now: i64,
+) -> Result<RepaymentQuote> {
+    // Insurance premium depends on pool solvency and the borrower's credit tier
+    let (credit_tier, _) = reputation::calculate_credit_tier(
+        borrower_profile.loans_repaid,
+        borrower_profile.defaults,
+    );
+    let mut quote = calculate_repayment_split(loan, global_state, credit_tier, now)?;
+
+    // The borrower covers transfer fees on each leg so every recipient nets its share
+    let mut total_charged = amount_with_transfer_fee(
+        mint,
+        quote.principal
+            .checked_add(quote.lender_interest)
+            .ok_or(CreditMarketError::MathOverflow)?,
+    )?;
+    for fee in [quote.insurance_fee, quote.protocol_fee] {
+        if fee > 0 {
+            total_charged = total_charged
+                .checked_add(amount_with_transfer_fee(mint, fee)?)
+                .ok_or(CreditMarketError::MathOverflow)?;
+        }
+    }
+    quote.total_charged = total_charged;
+
+    Ok(quote)
+}
+
+/// `calculate_repayment` for a borrower of `credit_tier`, before transfer fees:
+/// `total_charged` is left equal to `total`
+fn calculate_repayment_split(
+    loan: &Loan,
+    global_state: &GlobalState,
+    credit_tier: u8,
+    now: i64,
+) -> Result<RepaymentQuote> {
+    let min_interest_until = loan.start_time
+        .checked_add(loan.min_interest_secs as i64)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    let interest = loan_interest(loan, global_state, now.max(min_interest_until))?;
+    let origination_fee = (loan.principal as u128)
+        .checked_mul(loan.origination_fee_bps as u128)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .div_ceil(10000) as u64;
+    let prepayment_penalty = if now < loan.end_time {
+        (loan.principal as u128)
+            .checked_mul(loan.prepayment_penalty_bps as u128)
+            .ok_or(CreditMarketError::MathOverflow)?
+            .div_ceil(10000) as u64
+    } else {
+        0
+    };
+    let charges = interest
+        .checked_add(origination_fee)
+        .and_then(|c| c.checked_add(prepayment_penalty))
+        .ok_or(CreditMarketError::MathOverflow)?;
+
+    let insurance_premium_bps = calculate_insurance_premium_bps(global_state, credit_tier)?;
+
+    // Split interest and fees: lender, insurance premium, 1% protocol
+    let insurance_fee = charges
+        .checked_mul(insurance_premium_bps as u64)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .checked_div(10000)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    let protocol_fee = charges
+        .checked_mul(PROTOCOL_FEE_BPS)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .checked_div(10000)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    let lender_interest = charges
+        .checked_sub(insurance_fee)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .checked_sub(protocol_fee)
+        .ok_or(CreditMarketError::MathOverflow)?;
+    let total = loan.principal
+        .checked_add(charges)
+        .ok_or(CreditMarketError::MathOverflow)?;
+
+    Ok(RepaymentQuote {
+        principal: loan.principal,
+        interest,
+        origination_fee,
+        prepayment_penalty,
+        lender_interest,
+        insurance_fee,
+        insurance_premium_bps,
+        protocol_fee,
+        total,
+        total_charged: total,
+    })
+}
+
+/// Amount the borrower owed at `end_time`: principal plus interest over the full term.
+/// Variable-rate loans accrue up to the index at which they were closed out.
+fn calculate_amount_due_at_maturity(loan: &Loan, global_state: &GlobalState) -> Result<u64> {
+    let interest = loan_interest(loan, global_state, loan.end_time)?;
+    Ok(loan.principal
+        .checked_add(interest)
+        .ok_or(CreditMarketError::MathOverflow)?)
+}
+
+/// Split a post-default recovery between the lender and the insurance pool.
+/// The lender is owed `amount_due - insurance_payout` and is made whole first;
+/// the insurance pool is owed `insurance_payout` and receives what comes after.
+/// Returns `(to_lender, to_pool)`, capped at what is still outstanding.
+fn split_recovery(
+    amount: u64,
+    amount_due: u64,
+    insurance_payout: u64,
+    recovered_amount: u64,
+) -> Result<(u64, u64)> {
+    let lender_share = amount_due.saturating_sub(insurance_payout);
+    let lender_outstanding = lender_share.saturating_sub(recovered_amount);
+    let pool_recovered = recovered_amount.saturating_sub(lender_share);
+    let pool_outstanding = insurance_payout.saturating_sub(pool_recovered);
+
+    let to_lender = amount.min(lender_outstanding);
+    let to_pool = amount
+        .checked_sub(to_lender)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .min(pool_outstanding);
+
+    Ok((to_lender, to_pool))
+}
+
+// ============================================================================
+// Fixed-Point Math
+// ============================================================================
+
+/// 1.0 in 18-decimal fixed point
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// a * b / WAD, rounded up. `b` is split into whole and fractional WADs so the
+/// intermediate stays in range while `a` is below ~3.4e20.
+fn wad_mul_up(a: u128, b: u128) -> Result<u128> {
+    let whole = a.checked_mul(b / WAD).ok_or(CreditMarketError::MathOverflow)?;
+    let fraction = a
+        .checked_mul(b % WAD)
+        .ok_or(CreditMarketError::MathOverflow)?
+        .div_ceil(WAD);
+    Ok(whole.checked_add(fraction).ok_or(CreditMarketError::MathOverflow)?)
+}
+
+/// base^exp for a WAD `base`, by squaring, rounding up at every step
+fn wad_pow_up(base: u128, exp: u64) -> Result<u128> {
+    let mut result = WAD;
+    let mut base = base;
+    let mut exp = exp;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = wad_mul_up(result, base)?;
+        }
+        exp >>= 1;
+        if exp > 0 {
+            base = wad_mul_up(base, base)?;
+        }
+    }
+    Ok(result)
+}
+
+// ============================================================================
+// Oracle
+// ============================================================================
+
+// Pyth v2 price account layout
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+const PYTH_MAGIC_OFFSET: usize = 0;
+const PYTH_ACCOUNT_TYPE_OFFSET: usize = 8;
+const PYTH_EXPO_OFFSET: usize = 20;
+const PYTH_TIMESTAMP_OFFSET: usize = 96;
+const PYTH_AGG_PRICE_OFFSET: usize = 208;
+const PYTH_AGG_CONF_OFFSET: usize = 216;
+const PYTH_AGG_STATUS_OFFSET: usize = 224;
+const PYTH_MIN_ACCOUNT_LEN: usize = 240;
+
+/// Aggregate price read from a Pyth-style price account: `price * 10^expo` +/- `conf`
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    /// Read the aggregate price. The feed must be a price account that is trading.
+    pub fn load(feed: &AccountInfo) -> Result<Self> {
+        let data = feed.try_borrow_data()?;
+        require!(data.len() >= PYTH_MIN_ACCOUNT_LEN, CreditMarketError::InvalidPriceFeed);
+        require!(
+            u32::from_le_bytes(read_le(&data, PYTH_MAGIC_OFFSET)) == PYTH_MAGIC
+                && u32::from_le_bytes(read_le(&data, PYTH_ACCOUNT_TYPE_OFFSET))
+                    == PYTH_ACCOUNT_TYPE_PRICE,
+            CreditMarketError::InvalidPriceFeed
+        );
+        require!(
+            u32::from_le_bytes(read_le(&data, PYTH_AGG_STATUS_OFFSET)) == PYTH_STATUS_TRADING,
+            CreditMarketError::InvalidOraclePrice
+        );
+
+        Ok(Self {
+            price: i64::from_le_bytes(read_le(&data, PYTH_AGG_PRICE_OFFSET)),
+            conf: u64::from_le_bytes(read_le(&data, PYTH_AGG_CONF_OFFSET)),
+            expo: i32::from_le_bytes(read_le(&data, PYTH_EXPO_OFFSET)),
+            publish_time: i64::from_le_bytes(read_le(&data, PYTH_TIMESTAMP_OFFSET)),
+        })
+    }
+
+    /// Price less its confidence interval, once it passes the feed's staleness and
+    /// confidence limits. A publish time in the future is rejected.
+    pub fn conservative_price(&self, feed: &PriceFeed, now: i64) -> Result<u64> {
+        require!(self.price > 0, CreditMarketError::InvalidOraclePrice);
+        let age = now
+            .checked_sub(self.publish_time)
+            .ok_or(CreditMarketError::MathOverflow)?;
+        require!(age >= 0, CreditMarketError::InvalidOraclePrice);
+        require!(age <= feed.max_staleness_secs, CreditMarketError::OraclePriceStale);
+
+        let price = self.price as u64;
+        let confidence_bps = (self.conf as u128)
+            .checked_mul(10000)
+            .ok_or(CreditMarketError::MathOverflow)?
+            .checked_div(price as u128)
+            .ok_or(CreditMarketError::MathOverflow)?;
+        require!(
+            confidence_bps <= feed.max_confidence_bps as u128,
+            CreditMarketError::OracleConfidenceTooWide
+        );
+
+        Ok(price.saturating_sub(self.conf))
+    }
+}
+
+fn read_le<const N: usize>(data: &[u8], offset: usize) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    bytes.copy_from_slice(&data[offset..offset + N]);
+    bytes
+}
+
+// ============================================================================
+// Account Contexts
+// ============================================================================
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    /// Must be the program's upgrade authority, so markets can't be front-run per mint
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + GlobalState::INIT_SPACE,
+        seeds = [b"global_state", mint.key().as_ref()],
+        bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::CreditMarket>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ CreditMarketError::Unauthorized,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PostLendOffer<'info> {
+    #[account(mut)]
+    pub lender: Signer<'info>,
+    #[account(
+        init,
+        payer = lender,
+        space = 8 + LendOffer::INIT_SPACE,
+        seeds = [
+            b"offer",
+            mint.key().as_ref(),
+            lender.key().as_ref(),
+            global_state.next_loan_id.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub offer: Account<'info, LendOffer>,
+    #[account(
+        mut,
+        constraint = lender_usdc.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"escrow", mint.key().as_ref(), lender.key().as_ref()],
+        bump,
+        constraint = escrow_vault.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"global_state", mint.key().as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelLendOffer<'info> {
+    #[account(mut)]
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        constraint = offer.lender == lender.key() @ CreditMarketError::Unauthorized,
+    )]
+    pub offer: Account<'info, LendOffer>,
+    #[account(
+        mut,
+        constraint = lender_usdc.mint == offer.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"escrow", offer.mint.as_ref(), lender.key().as_ref()],
+        bump,
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"global_state", offer.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = offer.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct PostBorrowRequest<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        init,
+        payer = borrower,
+        space = 8 + BorrowRequest::INIT_SPACE,
+        seeds = [b"request", global_state.mint.as_ref(), borrower.key().as_ref()],
+        bump,
+    )]
+    pub request: Account<'info, BorrowRequest>,
+    #[account(
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptLendOffer<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = offer.is_active @ CreditMarketError::OfferNotActive,
+        constraint = offer.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub offer: Account<'info, LendOffer>,
+    #[account(
+        constraint = borrower_profile.owner == borrower.key() @ CreditMarketError::Unauthorized,
+    )]
+    pub borrower_profile: Account<'info, BorrowerProfile>,
+    #[account(
+        init,
+        payer = borrower,
+        space = 8 + Loan::INIT_SPACE,
+        seeds = [b"loan", mint.key().as_ref(), global_state.next_loan_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub loan: Account<'info, Loan>,
+    #[account(
+        mut,
+        seeds = [b"loan_vault", mint.key().as_ref(), global_state.next_loan_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = loan_vault.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub loan_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"escrow", mint.key().as_ref(), offer.lender.as_ref()],
+        bump,
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"global_state", mint.key().as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    /// Collateral accounts, only when posting collateral
+    pub collateral_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = borrower_collateral.owner == borrower.key() @ CreditMarketError::Unauthorized,
+    )]
+    pub borrower_collateral: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = borrower,
+        seeds = [b"collateral_vault", loan.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = loan,
+        token::token_program = collateral_token_program,
+    )]
+    pub collateral_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Checked against the market's price feed for `collateral_mint`
+    pub collateral_price_feed: Option<UncheckedAccount<'info>>,
+    pub collateral_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+pub struct RepayLoan<'info> {
+    /// The borrower or one of its session keys
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.is_outstanding() @ CreditMarketError::LoanNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+    /// Required when `authority` is a session key rather than the borrower
+    #[account(
+        mut,
+        seeds = [b"session", loan.borrower.as_ref(), authority.key().as_ref()],
+        bump = session_key.bump,
+    )]
+    pub session_key: Option<Account<'info, SessionKey>>,
+    #[account(
+        mut,
+        constraint = borrower_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub borrower_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = lender_usdc.owner == loan.lender,
+        constraint = lender_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
+    /// Insurance pool receives 10% of interest
+    #[account(
+        mut,
+        constraint = insurance_pool.key() == global_state.insurance_pool @ CreditMarketError::InvalidInsurancePool,
+        constraint = insurance_pool.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub insurance_pool: InterfaceAccount<'info, TokenAccount>,
+    /// Treasury receives 1% of interest (protocol fee)
+    #[account(
+        mut,
+        constraint = treasury.key() == global_state.treasury @ CreditMarketError::InvalidTreasury,
+        constraint = treasury.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        mut,
+        constraint = borrower_profile.owner == loan.borrower @ CreditMarketError::Unauthorized,
+    )]
+    pub borrower_profile: Account<'info, BorrowerProfile>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Collateral accounts, required if the loan holds collateral
+    #[account(mut)]
+    pub collateral_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub collateral_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = borrower_collateral.owner == loan.borrower @ CreditMarketError::Unauthorized,
+    )]
+    pub borrower_collateral: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub collateral_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteRepayment<'info> {
+    pub loan: Account<'info, Loan>,
+    #[account(
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        constraint = borrower_profile.owner == loan.borrower @ CreditMarketError::Unauthorized,
+    )]
+    pub borrower_profile: Account<'info, BorrowerProfile>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteTrade<'info> {
+    /// The borrower or one of its session keys
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.status == LoanStatus::Active @ CreditMarketError::LoanNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+    /// Required when `authority` is a session key rather than the borrower
+    #[account(
+        mut,
+        seeds = [b"session", loan.borrower.as_ref(), authority.key().as_ref()],
+        bump = session_key.bump,
+    )]
+    pub session_key: Option<Account<'info, SessionKey>>,
+    #[account(
+        mut,
+        constraint = loan_vault.key() == loan.vault,
+        constraint = loan_vault.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub loan_vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Target program for CPI
+    pub target_program: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = config.bump,
+    )]
     pub config: Account<'info, GlobalState>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidateLoan<'info> {
+    /// Anyone can be the liquidator (keeper bots)
+    pub liquidator: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.is_outstanding() @ CreditMarketError::LoanNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+    #[account(
+        mut,
+        constraint = loan_vault.key() == loan.vault,
+    )]
+    pub loan_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = lender_usdc.owner == loan.lender,
+        constraint = lender_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = borrower_profile.owner == loan.borrower,
+    )]
+    pub borrower_profile: Account<'info, BorrowerProfile>,
+    #[account(
+        mut,
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Credit accounts, required once the loan has disbursed credit
+    #[account(mut)]
+    pub credit_wrapper: Option<Account<'info, CreditWrapper>>,
+    #[account(mut)]
+    pub credit_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(mut)]
+    pub credit_reserve: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub borrower_credit: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub credit_token_program: Option<Program<'info, Token2022>>,
+    /// Collateral accounts, required if the loan holds collateral
+    #[account(mut)]
+    pub collateral_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub collateral_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = lender_collateral.owner == loan.lender @ CreditMarketError::Unauthorized,
+    )]
+    pub lender_collateral: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub collateral_token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarginCall<'info> {
+    /// Anyone can issue or clear a margin call
+    pub caller: Signer<'info>,
+    #[account(mut)]
+    pub loan: Account<'info, Loan>,
+    #[account(constraint = loan_vault.key() == loan.vault)]
+    pub loan_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// Required once the loan has disbursed credit
+    pub borrower_credit: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct GetLoanHealth<'info> {
+    pub loan: Account<'info, Loan>,
+    #[account(constraint = loan_vault.key() == loan.vault)]
+    pub loan_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// Required once the loan has disbursed credit
+    pub borrower_credit: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpLoan<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        constraint = loan.borrower == borrower.key() @ CreditMarketError::Unauthorized,
+        constraint = loan.is_outstanding() @ CreditMarketError::LoanNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+    #[account(
+        mut,
+        constraint = loan_vault.key() == loan.vault,
+    )]
+    pub loan_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = borrower_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub borrower_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct BidLiquidationAuction<'info> {
+    pub bidder: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.status == LoanStatus::Liquidated
+            && loan.auction_start > 0 @ CreditMarketError::AuctionNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+    #[account(
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    /// Loan position being sold, checked against `loan.positions`
+    #[account(
+        mut,
+        constraint = position_account.owner == loan.key() @ CreditMarketError::InvalidPosition,
+    )]
+    pub position_account: InterfaceAccount<'info, TokenAccount>,
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Checked against the market's price feed for `asset_mint`
+    pub price_feed: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        constraint = bidder_asset.mint == asset_mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub bidder_asset: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = bidder_usdc.owner == bidder.key() @ CreditMarketError::Unauthorized,
+        constraint = bidder_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub bidder_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = lender_usdc.owner == loan.lender @ CreditMarketError::Unauthorized,
+        constraint = lender_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = borrower_usdc.owner == loan.borrower @ CreditMarketError::Unauthorized,
+        constraint = borrower_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub borrower_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub asset_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct MarkDefault<'info> {
+    pub caller: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.is_outstanding() @ CreditMarketError::LoanNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+    #[account(
+        mut,
+        constraint = borrower_profile.owner == loan.borrower,
+    )]
+    pub borrower_profile: Account<'info, BorrowerProfile>,
+    #[account(
+        mut,
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// Collateral accounts, required if the loan holds collateral
+    #[account(mut)]
+    pub collateral_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub collateral_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = lender_collateral.owner == loan.lender @ CreditMarketError::Unauthorized,
+    )]
+    pub lender_collateral: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub collateral_token_program: Option<Interface<'info, TokenInterface>>,
+    /// CHECK: Price feed for the collateral mint, validated against `global_state.price_feeds`
+    pub collateral_price_feed: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminAction<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+        constraint = global_state.admin == admin.key() @ CreditMarketError::Unauthorized,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub new_admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+        constraint = global_state.pending_admin == new_admin.key() @ CreditMarketError::Unauthorized,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct QueueAction<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+        constraint = global_state.admin == admin.key() @ CreditMarketError::Unauthorized,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + QueuedAction::INIT_SPACE,
+        seeds = [
+            b"queued_action",
+            global_state.key().as_ref(),
+            global_state.next_action_id.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteAction<'info> {
+    /// Anyone can execute once the timelock has elapsed
+    pub executor: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [
+            b"queued_action",
+            global_state.key().as_ref(),
+            queued_action.id.to_le_bytes().as_ref(),
+        ],
+        bump = queued_action.bump,
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+    /// CHECK: Receives the queued action's rent, must match its proposer
+    #[account(mut, address = queued_action.proposer)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAction<'info> {
+    /// The admin, or the proposer of an approved multisig proposal for this call
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [
+            b"queued_action",
+            global_state.key().as_ref(),
+            queued_action.id.to_le_bytes().as_ref(),
+        ],
+        bump = queued_action.bump,
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+    /// CHECK: Receives the queued action's rent, must match its proposer
+    #[account(mut, address = queued_action.proposer)]
+    pub proposer: UncheckedAccount<'info>,
+    /// Required with `approval` when a multisig is admin
+    pub multisig: Option<Account<'info, Multisig>>,
+    #[account(mut)]
+    pub approval: Option<Account<'info, MultisigProposal>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMultisig<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + Multisig::INIT_SPACE,
+        seeds = [b"multisig", creator.key().as_ref()],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeMultisigAction<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"multisig", multisig.creator.as_ref()],
+        bump = multisig.bump,
+        constraint = multisig.signers.contains(&proposer.key()) @ CreditMarketError::NotMultisigSigner,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + MultisigProposal::INIT_SPACE,
+        seeds = [
+            b"multisig_proposal",
+            multisig.key().as_ref(),
+            multisig.next_proposal_id.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+    #[account(
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveMultisigAction<'info> {
+    pub approver: Signer<'info>,
+    #[account(
+        seeds = [b"multisig", multisig.creator.as_ref()],
+        bump = multisig.bump,
+        constraint = multisig.signers.contains(&approver.key()) @ CreditMarketError::NotMultisigSigner,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(
+        mut,
+        has_one = multisig,
+        constraint = proposal.owner_set_seqno == multisig.owner_set_seqno @ CreditMarketError::StaleProposal,
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+    #[account(
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+        constraint = global_state.key() == proposal.market @ CreditMarketError::MarketMismatch,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteMultisigAction<'info> {
+    /// Anyone can execute an approved proposal
+    pub executor: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"multisig", multisig.creator.as_ref()],
+        bump = multisig.bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+    #[account(
+        mut,
+        has_one = multisig,
+        has_one = proposer,
+        close = proposer,
+        constraint = proposal.owner_set_seqno == multisig.owner_set_seqno @ CreditMarketError::StaleProposal,
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+    /// CHECK: Receives the proposal's rent, must match its proposer
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+        constraint = global_state.key() == proposal.market @ CreditMarketError::MarketMismatch,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct CancelMultisigAction<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(
+        mut,
+        has_one = proposer @ CreditMarketError::Unauthorized,
+        close = proposer,
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+}
+
+#[derive(Accounts)]
+pub struct PauseAction<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+        constraint = (global_state.pause_authority == authority.key()
+            || global_state.admin == authority.key()) @ CreditMarketError::Unauthorized,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimInsurance<'info> {
+    #[account(mut)]
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.lender == lender.key() @ CreditMarketError::Unauthorized,
+        constraint = (loan.status == LoanStatus::Defaulted || loan.status == LoanStatus::Liquidated) @ CreditMarketError::LoanNotDefaulted,
+        constraint = !loan.insurance_claimed @ CreditMarketError::InsuranceAlreadyClaimed,
+    )]
+    pub loan: Account<'info, Loan>,
+    #[account(
+        mut,
+        seeds = [b"insurance_pool", loan.mint.as_ref()],
+        bump,
+        constraint = insurance_pool.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub insurance_pool: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = lender_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RepayDefaultedLoan<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.borrower == borrower.key() @ CreditMarketError::Unauthorized,
+        constraint = loan.status == LoanStatus::Defaulted @ CreditMarketError::LoanNotDefaulted,
+    )]
+    pub loan: Account<'info, Loan>,
+    #[account(
+        mut,
+        constraint = borrower_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub borrower_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = lender_usdc.owner == loan.lender,
+        constraint = lender_usdc.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
+    /// Insurance pool is reimbursed for what it paid the lender
+    #[account(
+        mut,
+        constraint = insurance_pool.key() == global_state.insurance_pool @ CreditMarketError::InvalidInsurancePool,
+        constraint = insurance_pool.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub insurance_pool: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(key: Pubkey)]
+pub struct RegisterSessionKey<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        init,
+        payer = borrower,
+        space = 8 + SessionKey::INIT_SPACE,
+        seeds = [b"session", borrower.key().as_ref(), key.as_ref()],
+        bump,
+    )]
+    pub session_key: Account<'info, SessionKey>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSessionKey<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        close = borrower,
+        has_one = borrower @ CreditMarketError::Unauthorized,
+        seeds = [b"session", borrower.key().as_ref(), session_key.key.as_ref()],
+        bump = session_key.bump,
+    )]
+    pub session_key: Account<'info, SessionKey>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveMerchant<'info> {
+    #[account(mut)]
+    pub approver: Signer<'info>,
+    #[account(
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        constraint = merchant_token.mint == global_state.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub merchant_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = approver,
+        space = 8 + Merchant::INIT_SPACE,
+        seeds = [
+            b"merchant",
+            global_state.key().as_ref(),
+            approver.key().as_ref(),
+            merchant_token.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub merchant: Account<'info, Merchant>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeMerchant<'info> {
+    #[account(mut)]
+    pub approver: Signer<'info>,
+    #[account(
+        mut,
+        close = approver,
+        has_one = approver @ CreditMarketError::Unauthorized,
+        seeds = [
+            b"merchant",
+            merchant.market.as_ref(),
+            approver.key().as_ref(),
+            merchant.token_account.as_ref(),
+        ],
+        bump = merchant.bump,
+    )]
+    pub merchant: Account<'info, Merchant>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveAdminMerchant<'info> {
+    /// The admin, or the proposer of an approved multisig proposal for this call
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        constraint = merchant_token.mint == global_state.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub merchant_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Merchant::INIT_SPACE,
+        seeds = [
+            b"merchant",
+            global_state.key().as_ref(),
+            global_state.admin.as_ref(),
+            merchant_token.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub merchant: Account<'info, Merchant>,
+    pub system_program: Program<'info, System>,
+    /// Required with `approval` when a multisig is admin
+    pub multisig: Option<Account<'info, Multisig>>,
+    #[account(mut)]
+    pub approval: Option<Account<'info, MultisigProposal>>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAdminMerchant<'info> {
+    /// The admin, or the proposer of an approved multisig proposal for this call
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        mut,
+        close = authority,
+        constraint = merchant.approver == global_state.admin @ CreditMarketError::Unauthorized,
+        seeds = [
+            b"merchant",
+            global_state.key().as_ref(),
+            global_state.admin.as_ref(),
+            merchant.token_account.as_ref(),
+        ],
+        bump = merchant.bump,
+    )]
+    pub merchant: Account<'info, Merchant>,
+    /// Required with `approval` when a multisig is admin
+    pub multisig: Option<Account<'info, Multisig>>,
+    #[account(mut)]
+    pub approval: Option<Account<'info, MultisigProposal>>,
+}
+
+#[derive(Accounts)]
+pub struct SetLoanMerchants<'info> {
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.lender == lender.key() @ CreditMarketError::Unauthorized,
+        constraint = loan.status == LoanStatus::Active @ CreditMarketError::LoanNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+}
+
+#[derive(Accounts)]
+pub struct SetOfferCollateralTerms<'info> {
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        has_one = lender @ CreditMarketError::Unauthorized,
+        constraint = offer.is_active @ CreditMarketError::OfferNotActive,
+    )]
+    pub offer: Account<'info, LendOffer>,
+}
+
+#[derive(Accounts)]
+pub struct SetOfferWarningThreshold<'info> {
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        has_one = lender @ CreditMarketError::Unauthorized,
+        constraint = offer.is_active @ CreditMarketError::OfferNotActive,
+    )]
+    pub offer: Account<'info, LendOffer>,
+}
+
+#[derive(Accounts)]
+pub struct SetOfferRepaymentTerms<'info> {
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        has_one = lender @ CreditMarketError::Unauthorized,
+        constraint = offer.is_active @ CreditMarketError::OfferNotActive,
+    )]
+    pub offer: Account<'info, LendOffer>,
+}
+
+#[derive(Accounts)]
+pub struct SetOfferFlashTerms<'info> {
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        has_one = lender @ CreditMarketError::Unauthorized,
+        constraint = offer.is_active @ CreditMarketError::OfferNotActive,
+    )]
+    pub offer: Account<'info, LendOffer>,
+}
+
+#[derive(Accounts)]
+pub struct FlashBorrow<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = offer.is_active @ CreditMarketError::OfferNotActive,
+        constraint = offer.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub offer: Account<'info, LendOffer>,
+    #[account(
+        mut,
+        seeds = [b"escrow", mint.key().as_ref(), offer.lender.as_ref()],
+        bump,
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = borrower_usdc.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub borrower_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [b"global_state", mint.key().as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: Instructions sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+/// `offer` must stay the second account, `flash_borrow` finds the repay by it
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    pub repayer: Signer<'info>,
+    #[account(
+        mut,
+        constraint = offer.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub offer: Account<'info, LendOffer>,
+    #[account(
+        mut,
+        seeds = [b"escrow", mint.key().as_ref(), offer.lender.as_ref()],
+        bump,
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = repayer_usdc.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub repayer_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = lender_usdc.owner == offer.lender @ CreditMarketError::Unauthorized,
+        constraint = lender_usdc.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub lender_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = insurance_pool.key() == global_state.insurance_pool @ CreditMarketError::InvalidInsurancePool,
+    )]
+    pub insurance_pool: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"global_state", mint.key().as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetOfferRateMode<'info> {
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        has_one = lender @ CreditMarketError::Unauthorized,
+        constraint = offer.is_active @ CreditMarketError::OfferNotActive,
+    )]
+    pub offer: Account<'info, LendOffer>,
+}
+
+#[derive(Accounts)]
+pub struct SetLoanSpendPolicy<'info> {
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.lender == lender.key() @ CreditMarketError::Unauthorized,
+        constraint = loan.status == LoanStatus::Active @ CreditMarketError::LoanNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+}
+
+#[derive(Accounts)]
+pub struct PayMerchant<'info> {
+    /// The borrower or one of its session keys
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.status == LoanStatus::Active @ CreditMarketError::LoanNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+    /// Required when `authority` is a session key rather than the borrower
+    #[account(
+        mut,
+        seeds = [b"session", loan.borrower.as_ref(), authority.key().as_ref()],
+        bump = session_key.bump,
+    )]
+    pub session_key: Option<Account<'info, SessionKey>>,
+    #[account(
+        mut,
+        constraint = loan_vault.key() == loan.vault,
+    )]
+    pub loan_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = merchant_token.mint == loan.mint @ CreditMarketError::MintMismatch,
+    )]
+    pub merchant_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [
+            b"merchant",
+            global_state.key().as_ref(),
+            merchant.approver.as_ref(),
+            merchant_token.key().as_ref(),
+        ],
+        bump = merchant.bump,
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AddLoanPosition<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.borrower == borrower.key() @ CreditMarketError::Unauthorized,
+        constraint = loan.status == LoanStatus::Active @ CreditMarketError::LoanNotActive,
+    )]
+    pub loan: Account<'info, Loan>,
+    #[account(
+        constraint = position_token.owner == loan.key() @ CreditMarketError::InvalidPosition,
+        constraint = position_token.mint == position_mint.key() @ CreditMarketError::MintMismatch,
+        // The vault, collateral and credit are already counted by loan_health
+        constraint = position_token.key() != loan.vault @ CreditMarketError::InvalidPosition,
+        constraint = position_token.key() != loan.collateral_vault
+            @ CreditMarketError::InvalidPosition,
+        constraint = position_token.key() != loan.credit_account
+            @ CreditMarketError::InvalidPosition,
+    )]
+    pub position_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = position_mint.key() != loan.mint @ CreditMarketError::InvalidPosition,
+    )]
+    pub position_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveLoanPosition<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = loan.borrower == borrower.key() @ CreditMarketError::Unauthorized,
+    )]
+    pub loan: Account<'info, Loan>,
+    pub position_token: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeCreditWrapper<'info> {
+    /// The admin, or the proposer of an approved multisig proposal for this call
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"global_state", mint.key().as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + CreditWrapper::INIT_SPACE,
+        seeds = [b"credit_wrapper", global_state.key().as_ref()],
+        bump,
+    )]
+    pub credit_wrapper: Account<'info, CreditWrapper>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"credit_reserve", global_state.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = credit_wrapper,
+        token::token_program = token_program,
+    )]
+    pub credit_reserve: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Created here as a Token-2022 mint with permanent delegate and transfer hook
+    #[account(
+        mut,
+        seeds = [b"credit_mint", global_state.key().as_ref()],
+        bump,
+    )]
+    pub credit_mint: UncheckedAccount<'info>,
+    /// CHECK: Created and validated by the transfer hook program
+    #[account(mut)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    /// CHECK: Transfer hook program set on the credit mint
+    #[account(executable)]
+    pub transfer_hook_program: UncheckedAccount<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub credit_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+    /// Required with `approval` when a multisig is admin
+    pub multisig: Option<Account<'info, Multisig>>,
+    #[account(mut)]
+    pub approval: Option<Account<'info, MultisigProposal>>,
 }
 
 #[derive(Accounts)]
-pub struct LiquidateLoan<'info> {
-    /// Anyone can be the liquidator (keeper bots)
-    pub liquidator: Signer<'info>,
+#[instruction(address: Pubkey)]
+pub struct AddRedemptionAddress<'info> {
+    /// The admin, or the proposer of an approved multisig proposal for this call
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        seeds = [b"credit_wrapper", global_state.key().as_ref()],
+        bump = credit_wrapper.bump,
+    )]
+    pub credit_wrapper: Account<'info, CreditWrapper>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + RedemptionAddress::INIT_SPACE,
+        seeds = [b"redemption", credit_wrapper.key().as_ref(), address.as_ref()],
+        bump,
+    )]
+    pub redemption_address: Account<'info, RedemptionAddress>,
+    pub system_program: Program<'info, System>,
+    /// Required with `approval` when a multisig is admin
+    pub multisig: Option<Account<'info, Multisig>>,
+    #[account(mut)]
+    pub approval: Option<Account<'info, MultisigProposal>>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveRedemptionAddress<'info> {
+    /// The admin, or the proposer of an approved multisig proposal for this call
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"global_state", global_state.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        seeds = [b"credit_wrapper", global_state.key().as_ref()],
+        bump = credit_wrapper.bump,
+    )]
+    pub credit_wrapper: Account<'info, CreditWrapper>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"redemption",
+            credit_wrapper.key().as_ref(),
+            redemption_address.address.as_ref(),
+        ],
+        bump = redemption_address.bump,
+    )]
+    pub redemption_address: Account<'info, RedemptionAddress>,
+    /// Required with `approval` when a multisig is admin
+    pub multisig: Option<Account<'info, Multisig>>,
+    #[account(mut)]
+    pub approval: Option<Account<'info, MultisigProposal>>,
+}
+
+#[derive(Accounts)]
+pub struct Wrap<'info> {
+    pub holder: Signer<'info>,
+    #[account(
+        mut,
+        constraint = holder_usdc.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub holder_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = holder_credit.mint == credit_mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub holder_credit: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"credit_wrapper", credit_wrapper.market.as_ref()],
+        bump = credit_wrapper.bump,
+    )]
+    pub credit_wrapper: Account<'info, CreditWrapper>,
+    #[account(mut, address = credit_wrapper.credit_mint @ CreditMarketError::MintMismatch)]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, address = credit_wrapper.reserve @ CreditMarketError::InvalidCreditReserve)]
+    pub credit_reserve: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = credit_wrapper.underlying_mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub credit_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct Unwrap<'info> {
+    pub holder: Signer<'info>,
+    #[account(
+        mut,
+        constraint = holder_credit.mint == credit_mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub holder_credit: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = destination.mint == mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    /// Must exist for `destination` to receive redemptions
+    #[account(
+        seeds = [b"redemption", credit_wrapper.key().as_ref(), destination.key().as_ref()],
+        bump = redemption_address.bump,
+    )]
+    pub redemption_address: Account<'info, RedemptionAddress>,
+    #[account(
+        mut,
+        seeds = [b"credit_wrapper", credit_wrapper.market.as_ref()],
+        bump = credit_wrapper.bump,
+    )]
+    pub credit_wrapper: Account<'info, CreditWrapper>,
+    #[account(mut, address = credit_wrapper.credit_mint @ CreditMarketError::MintMismatch)]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, address = credit_wrapper.reserve @ CreditMarketError::InvalidCreditReserve)]
+    pub credit_reserve: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = credit_wrapper.underlying_mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub credit_token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct DisburseLoanCredit<'info> {
+    pub borrower: Signer<'info>,
     #[account(
         mut,
+        constraint = loan.borrower == borrower.key() @ CreditMarketError::Unauthorized,
         constraint = loan.status == LoanStatus::Active @ CreditMarketError::LoanNotActive,
     )]
     pub loan: Account<'info, Loan>,
@@ -849,276 +5626,984 @@ pub struct LiquidateLoan<'info> {
         mut,
         constraint = loan_vault.key() == loan.vault,
     )]
-    pub loan_vault: Account<'info, TokenAccount>,
+    pub loan_vault: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
-        constraint = lender_usdc.owner == loan.lender,
+        constraint = borrower_credit.owner == borrower.key() @ CreditMarketError::Unauthorized,
+        constraint = borrower_credit.mint == credit_mint.key() @ CreditMarketError::MintMismatch,
     )]
-    pub lender_usdc: Account<'info, TokenAccount>,
+    pub borrower_credit: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
-        constraint = borrower_profile.owner == loan.borrower,
+        seeds = [b"credit_wrapper", global_state.key().as_ref()],
+        bump = credit_wrapper.bump,
     )]
-    pub borrower_profile: Account<'info, BorrowerProfile>,
-    pub token_program: Program<'info, Token>,
+    pub credit_wrapper: Account<'info, CreditWrapper>,
+    #[account(mut, address = credit_wrapper.credit_mint @ CreditMarketError::MintMismatch)]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, address = credit_wrapper.reserve @ CreditMarketError::InvalidCreditReserve)]
+    pub credit_reserve: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub credit_token_program: Program<'info, Token2022>,
 }
 
 #[derive(Accounts)]
-pub struct MarkDefault<'info> {
-    pub caller: Signer<'info>,
+pub struct UnwrapToLoan<'info> {
+    pub borrower: Signer<'info>,
     #[account(
         mut,
-        constraint = loan.status == LoanStatus::Active @ CreditMarketError::LoanNotActive,
+        constraint = loan.borrower == borrower.key() @ CreditMarketError::Unauthorized,
+        constraint = loan.is_outstanding() @ CreditMarketError::LoanNotActive,
     )]
     pub loan: Account<'info, Loan>,
     #[account(
         mut,
-        constraint = borrower_profile.owner == loan.borrower,
+        constraint = loan_vault.key() == loan.vault,
     )]
-    pub borrower_profile: Account<'info, BorrowerProfile>,
+    pub loan_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = borrower_credit.mint == credit_mint.key() @ CreditMarketError::MintMismatch,
+    )]
+    pub borrower_credit: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"credit_wrapper", global_state.key().as_ref()],
+        bump = credit_wrapper.bump,
+    )]
+    pub credit_wrapper: Account<'info, CreditWrapper>,
+    #[account(mut, address = credit_wrapper.credit_mint @ CreditMarketError::MintMismatch)]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, address = credit_wrapper.reserve @ CreditMarketError::InvalidCreditReserve)]
+    pub credit_reserve: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [b"global_state", loan.mint.as_ref()],
+        bump = global_state.bump,
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(address = loan.mint @ CreditMarketError::MintMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub credit_token_program: Program<'info, Token2022>,
+}
+
+// ============================================================================
+// Account Structures
+// ============================================================================
+
+#[account]
+#[derive(InitSpace)]
+pub struct GlobalState {
+    pub admin: Pubkey,
+    pub mint: Pubkey,                      // Market mint, every vault and token account uses it
+    pub next_loan_id: u64,
+    pub fee_bps: u16,                      // Protocol fee: 1% (100 bps)
+    pub insurance_fee_bps: u16,            // Insurance fee: 10% (1000 bps)
+    pub insurance_pool: Pubkey,            // Insurance pool token account
+    pub treasury: Pubkey,                  // Protocol treasury token account
+    pub total_insurance_collected: u64,    // Total insurance fees collected
+    pub total_insurance_claimed: u64,      // Total insurance payouts claimed
+    pub solvency_ratio_bps: u32,           // Insurance collected / claimed, drives premiums
+    pub min_premium_bps: u16,              // Premium curve floor
+    pub max_premium_bps: u16,              // Premium curve ceiling
+    pub target_solvency_bps: u32,          // Solvency ratio at which premium == insurance_fee_bps
+    pub tier_discount_bps: u16,            // Premium discount per credit tier above 1
+    pub pending_admin: Pubkey,             // Proposed admin awaiting accept_admin
+    pub timelock_delay_secs: i64,          // Delay before queued governance actions can execute
+    pub next_action_id: u64,
+    pub pause_authority: Pubkey,           // Can pause/unpause alongside the admin
+    pub paused_flags: u8,                  // PAUSE_* bitflags
+    pub breaker_window_secs: i64,          // Circuit breaker window (0 = disabled)
+    pub breaker_max_defaults: u32,         // Defaults allowed per window before tripping
+    #[max_len(BREAKER_HISTORY_LEN)]
+    pub breaker_recent_defaults: Vec<i64>, // Latest default times, oldest first
+    #[max_len(MAX_WHITELISTED_PROGRAMS)]
+    pub whitelisted_programs: Vec<Pubkey>,
+    #[max_len(MAX_PRICE_FEEDS)]
+    pub price_feeds: Vec<PriceFeed>,       // Oracles for valuing loan positions, by mint
+    pub rate_model: RateModel,             // Variable-rate curve
+    pub borrow_index: u128,                // Variable-rate growth, BORROW_INDEX_ONE at launch
+    pub index_updated_at: i64,
+    pub total_offered: u64,                // Escrowed in active offers
+    pub total_borrowed: u64,               // Principal of outstanding loans
+    pub bump: u8,
+}
+
+impl GlobalState {
+    pub fn is_paused(&self, flag: u8) -> bool {
+        self.paused_flags & flag != 0
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct QueuedAction {
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub action: GovernanceAction,
+    pub queued_at: i64,
+    pub eta: i64,                          // Earliest execution time
+    pub bump: u8,
+}
+
+/// M-of-N signer set that can hold the admin role
+#[account]
+#[derive(InitSpace)]
+pub struct Multisig {
+    pub creator: Pubkey,
+    #[max_len(MAX_MULTISIG_SIGNERS)]
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub owner_set_seqno: u32,              // Bumped when signers change, invalidating open proposals
+    pub next_proposal_id: u64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct MultisigProposal {
+    pub multisig: Pubkey,
+    pub market: Pubkey,                    // GlobalState the proposal applies to
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub action: MultisigAction,
+    #[max_len(MAX_MULTISIG_SIGNERS)]
+    pub approvals: Vec<Pubkey>,
+    pub owner_set_seqno: u32,
+    pub created_at: i64,
+    pub eta: i64,                          // 0 until the threshold is met
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct LendOffer {
+    pub lender: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub min_rate_bps: u16,
+    pub max_duration_secs: u64,
+    pub min_reputation: u16,
+    pub liquidation_threshold_bps: u16,  // NEW: e.g., 8000 = 80%
+    pub warning_threshold_bps: u16,      // Margin call below this, >= liquidation threshold
+    pub spend_policy: SpendPolicy,       // Copied to the loan on acceptance
+    #[max_len(MAX_COLLATERAL_MINTS)]
+    pub collateral_mints: Vec<Pubkey>,   // Empty = uncollateralized only
+    pub ltv_bps: u16,                    // Borrowing power per unit of collateral value
+    pub rate_mode: RateMode,             // Copied to the loan on acceptance
+    pub min_interest_secs: u64,          // Interest charged for at least this long
+    pub origination_fee_bps: u16,        // Flat fee on principal, charged at repayment
+    pub prepayment_penalty_bps: u16,     // Charged on principal if repaid before end_time
+    pub flash_loans_enabled: bool,
+    pub flash_fee_bps: u16,
+    pub flash_loan_amount: u64,          // Lent by flash_borrow, awaiting flash_repay
+    pub is_active: bool,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct BorrowRequest {
+    pub borrower: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub max_rate_bps: u16,
+    pub duration_secs: u64,
+    pub is_active: bool,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Loan {
+    pub id: u64,
+    pub lender: Pubkey,
+    pub borrower: Pubkey,
+    pub mint: Pubkey,
+    pub principal: u64,
+    pub rate_bps: u16,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub status: LoanStatus,
+    pub vault: Pubkey,
+    pub liquidation_threshold_bps: u16,  // Copied from offer at loan creation
+    pub warning_threshold_bps: u16,      // Copied from offer at loan creation
+    pub margin_call_deadline: i64,       // End of the cure window while in MarginCall
+    pub insurance_claimed: bool,         // Whether lender has claimed insurance for default
+    pub insurance_payout: u64,           // Amount paid to the lender from the insurance pool
+    pub recovered_amount: u64,           // Total recovered by the lender after liquidation or default
+    pub credit_outstanding: u64,         // Disbursed as credit and not yet unwrapped back
+    pub credit_account: Pubkey,          // Borrower credit account, set on first disbursement
+    pub total_spent: u64,                // Paid to merchants from the loan vault
+    #[max_len(MAX_LOAN_MERCHANTS)]
+    pub allowed_merchants: Vec<Pubkey>,  // Merchant token accounts set by the lender, empty = any approved
+    pub spend_policy: SpendPolicy,       // Enforced by execute_trade
+    pub trade_day_start: i64,
+    pub trades_today: u16,
+    #[max_len(MAX_LOAN_POSITIONS)]
+    pub positions: Vec<LoanPosition>,    // Non-vault token accounts owned by the loan
+    pub collateral_mint: Pubkey,         // Default if the loan has no collateral
+    pub collateral_vault: Pubkey,
+    pub collateral_amount: u64,          // Deposited, 0 once released or seized
+    pub auction_start: i64,              // Liquidation auction start, 0 = no auction
+    pub auction_debt_remaining: u64,     // Owed to the lender from auction proceeds
+    pub rate_mode: RateMode,
+    pub entry_borrow_index: u128,        // Market borrow index when the loan started
+    pub exit_borrow_index: u128,         // Index when the loan closed, 0 while outstanding
+    pub min_interest_secs: u64,          // Repayment terms, copied from the offer
+    pub origination_fee_bps: u16,
+    pub prepayment_penalty_bps: u16,
+    pub bump: u8,
+}
+
+impl Loan {
+    /// Funds are still out with the borrower: active or under a margin call
+    pub fn is_outstanding(&self) -> bool {
+        matches!(self.status, LoanStatus::Active | LoanStatus::MarginCall)
+    }
+
+    /// Whether `liquidate_loan` would accept the loan at `health_factor_bps`: past due, or
+    /// unhealthy with a margin call whose cure window has ended
+    pub fn is_liquidatable(&self, health_factor_bps: u16, now: i64) -> bool {
+        if !self.is_outstanding() {
+            return false;
+        }
+        let is_past_due = now > self.end_time;
+        let cure_window_over =
+            self.status == LoanStatus::MarginCall && now >= self.margin_call_deadline;
+        is_past_due || (health_factor_bps < self.liquidation_threshold_bps && cure_window_over)
+    }
+}
+
+/// Scoped key an agent can use in place of the borrower
+#[account]
+#[derive(InitSpace)]
+pub struct SessionKey {
+    pub borrower: Pubkey,
+    pub key: Pubkey,
+    pub expires_at: i64,
+    pub allowed_actions: u8,               // SESSION_* bitflags
+    pub spend_cap: u64,                    // Lifetime cap on loan vault outflows
+    pub spent: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+/// Merchant token account approved by `approver`, honoured when that is the market
+/// admin or the paying loan's lender
+#[account]
+#[derive(InitSpace)]
+pub struct Merchant {
+    pub market: Pubkey,
+    pub approver: Pubkey,
+    pub token_account: Pubkey,
+    #[max_len(MAX_MERCHANT_LABEL_LEN)]
+    pub label: String,
+    pub added_at: i64,
+    pub bump: u8,
+}
+
+/// Issues the market's credit token 1:1 against USDC held in `reserve`
+#[account]
+#[derive(InitSpace)]
+pub struct CreditWrapper {
+    pub market: Pubkey,                    // GlobalState this wrapper belongs to
+    pub underlying_mint: Pubkey,
+    pub credit_mint: Pubkey,               // Token-2022, wrapper is mint authority and permanent delegate
+    pub reserve: Pubkey,                   // Underlying token account backing the credit supply
+    pub transfer_hook_program: Pubkey,
+    pub total_wrapped: u64,                // Credit minted and not yet burned
+    pub bump: u8,
+}
+
+/// Token account that credit may be unwrapped to
+#[account]
+#[derive(InitSpace)]
+pub struct RedemptionAddress {
+    pub wrapper: Pubkey,
+    pub address: Pubkey,
+    pub added_at: i64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct BorrowerProfile {
+    pub owner: Pubkey,
+    pub reputation_score: u16,
+    pub loans_repaid: u32,
+    pub defaults: u32,
+    pub total_borrowed: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+// ============================================================================
+// Enums
+// ============================================================================
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum LoanStatus {
+    Open,       // Offer/request created, not yet matched
+    Active,     // Loan is active, borrower has funds
+    Repaid,     // Borrower repaid in full
+    Defaulted,  // Past due, marked as default
+    Liquidated, // Force-closed due to past due or unhealthy position
+    Recovered,  // Defaulted, then repaid in full to the lender and insurance pool
+    MarginCall, // Below the warning threshold, borrower has a cure window
+}
+
+/// How a loan accrues interest
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum RateMode {
+    Fixed,      // Simple interest at the offer's min_rate_bps
+    Variable,   // Market borrow index, repriced as utilization changes
+    Compound,   // Offer's min_rate_bps compounded every second
+}
+
+/// Kinked utilization curve for variable-rate loans, annual rates in bps
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub struct RateModel {
+    pub base_rate_bps: u16,
+    pub slope1_bps: u16,                 // Added linearly up to optimal utilization
+    pub slope2_bps: u16,                 // Added linearly from optimal to full utilization
+    pub optimal_utilization_bps: u16,
+}
+
+/// Returned by `quote_repayment`, amounts in market-mint units
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RepaymentQuote {
+    pub principal: u64,
+    pub interest: u64,                   // At least min_interest_secs worth
+    pub origination_fee: u64,
+    pub prepayment_penalty: u64,         // 0 from end_time on
+    pub lender_interest: u64,            // Lender's share of interest and fees
+    pub insurance_fee: u64,
+    pub insurance_premium_bps: u16,
+    pub protocol_fee: u64,
+    pub total: u64,                      // Principal plus interest and fees
+    pub total_charged: u64,              // Total plus any transfer fees, checked against max_total
+}
+
+/// Returned by `get_loan_health`, amounts in market-mint units
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LoanHealthInfo {
+    pub principal: u64,
+    pub accrued_interest: u64,
+    pub amount_due: u64,                 // Principal plus interest accrued so far
+    pub vault_balance: u64,
+    pub credit_held: u64,                // Disbursed credit the borrower still holds
+    pub positions_value: u64,            // Oracle value of the loan's positions
+    pub portfolio_value: u64,            // Vault + credit held + positions
+    pub health_factor_bps: u16,
+    pub warning_threshold_bps: u16,
+    pub liquidation_threshold_bps: u16,
+    pub is_past_due: bool,
+    pub seconds_until_end: i64,          // Negative once past due
+    pub margin_call_deadline: i64,       // 0 unless under a margin call
+    pub is_liquidatable: bool,
 }
 
-#[derive(Accounts)]
-pub struct AdminAction<'info> {
-    pub admin: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"global_state"],
-        bump = global_state.bump,
-        constraint = global_state.admin == admin.key() @ CreditMarketError::Unauthorized,
-    )]
-    pub global_state: Account<'info, GlobalState>,
+/// Oracle for one mint: `feed` is a Pyth-style price account owned by `oracle_program`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub struct PriceFeed {
+    pub mint: Pubkey,
+    pub oracle_program: Pubkey,
+    pub feed: Pubkey,
+    pub max_staleness_secs: i64,
+    pub max_confidence_bps: u16,           // Largest conf / price accepted
 }
 
-#[derive(Accounts)]
-pub struct ClaimInsurance<'info> {
-    #[account(mut)]
-    pub lender: Signer<'info>,
-    #[account(
-        mut,
-        constraint = loan.lender == lender.key() @ CreditMarketError::Unauthorized,
-        constraint = (loan.status == LoanStatus::Defaulted || loan.status == LoanStatus::Liquidated) @ CreditMarketError::LoanNotDefaulted,
-        constraint = !loan.insurance_claimed @ CreditMarketError::InsuranceAlreadyClaimed,
-    )]
-    pub loan: Account<'info, Loan>,
-    #[account(
-        mut,
-        seeds = [b"insurance_pool"],
-        bump,
-    )]
-    pub insurance_pool: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub lender_usdc: Account<'info, TokenAccount>,
-    #[account(
-        mut,
-        seeds = [b"global_state"],
-        bump = global_state.bump,
-    )]
-    pub global_state: Account<'info, GlobalState>,
-    pub token_program: Program<'info, Token>,
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub struct LoanPosition {
+    pub token_account: Pubkey,
+    pub mint: Pubkey,
+    pub decimals: u8,
+}
+
+/// Lender limits on how `execute_trade` may use a loan's vault. 0 / empty = unlimited.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, PartialEq, Eq, InitSpace)]
+pub struct SpendPolicy {
+    pub max_notional_per_trade: u64,
+    pub max_trades_per_day: u16,
+    pub max_vault_fraction_bps: u16,       // Largest share of the vault a single trade may use
+    #[max_len(MAX_POLICY_PROGRAMS)]
+    pub allowed_programs: Vec<Pubkey>,     // Narrows the market whitelist for this loan
+}
+
+/// Admin changes that must pass through the timelock
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum GovernanceAction {
+    UpdateFeeRates {
+        insurance_fee_bps: Option<u16>,
+        protocol_fee_bps: Option<u16>,
+    },
+    AddWhitelistedProgram { program_id: Pubkey },
+    RemoveWhitelistedProgram { program_id: Pubkey },
+    SetPriceFeed { feed: PriceFeed },
+    RemovePriceFeed { mint: Pubkey },
+    SetPauseAuthority { pause_authority: Pubkey },
+    ConfigureCircuitBreaker { window_secs: i64, max_defaults: u32 },
+    UpdatePremiumCurve {
+        min_premium_bps: Option<u16>,
+        max_premium_bps: Option<u16>,
+        target_solvency_bps: Option<u32>,
+        tier_discount_bps: Option<u16>,
+    },
+    SetRateModel { model: RateModel },
+    SetTimelockDelay { delay_secs: i64 },
+}
+
+/// Actions a multisig can take as admin
+/// The last six approve a call to the matching admin instruction, made by the proposer
+/// with the approved proposal, rather than being run by `execute_multisig_action`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum MultisigAction {
+    Governance(GovernanceAction),
+    ProposeAdmin { new_admin: Pubkey },
+    AcceptAdmin,
+    ChangeSigners {
+        #[max_len(MAX_MULTISIG_SIGNERS)]
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    },
+    CancelAction { action_id: u64 },
+    InitializeCreditWrapper { transfer_hook_program: Pubkey },
+    AddRedemptionAddress { address: Pubkey },
+    RemoveRedemptionAddress { address: Pubkey },
+    ApproveMerchant {
+        token_account: Pubkey,
+        #[max_len(MAX_MERCHANT_LABEL_LEN)]
+        label: String,
+    },
+    RevokeMerchant { token_account: Pubkey },
+}
+
+impl Default for LoanStatus {
+    fn default() -> Self {
+        LoanStatus::Open
+    }
 }
 
 // ============================================================================
-// Account Structures
+// Events
 // ============================================================================
 
-#[account]
-#[derive(InitSpace)]
-pub struct GlobalState {
+#[event]
+pub struct MarketInitialized {
     pub admin: Pubkey,
-    pub next_loan_id: u64,
-    pub fee_bps: u16,                      // Protocol fee: 1% (100 bps)
-    pub insurance_fee_bps: u16,            // Insurance fee: 10% (1000 bps)
-    pub insurance_pool: Pubkey,            // Insurance pool token account
-    pub treasury: Pubkey,                  // Protocol treasury token account
-    pub total_insurance_collected: u64,    // Total insurance fees collected
-    pub total_insurance_claimed: u64,      // Total insurance payouts claimed
-    #[max_len(20)]
-    pub whitelisted_programs: Vec<Pubkey>,
-    pub bump: u8,
+    pub mint: Pubkey,
+    pub insurance_pool: Pubkey,
+    pub treasury: Pubkey,
+    pub timestamp: i64,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct LendOffer {
+#[event]
+pub struct LendOfferPosted {
     pub lender: Pubkey,
+    pub mint: Pubkey,
     pub amount: u64,
     pub min_rate_bps: u16,
     pub max_duration_secs: u64,
-    pub min_reputation: u16,
-    pub liquidation_threshold_bps: u16,  // NEW: e.g., 8000 = 80%
-    pub is_active: bool,
-    pub created_at: i64,
-    pub bump: u8,
+    pub liquidation_threshold_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LendOfferCancelled {
+    pub lender: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BorrowRequestPosted {
+    pub borrower: Pubkey,
+    pub amount: u64,
+    pub max_rate_bps: u16,
+    pub duration_secs: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoanCreated {
+    pub loan_id: u64,
+    pub lender: Pubkey,
+    pub borrower: Pubkey,
+    pub mint: Pubkey,
+    pub principal: u64,
+    pub rate_bps: u16,
+    pub rate_mode: RateMode,
+    pub end_time: i64,
+    pub liquidation_threshold_bps: u16,
+    pub collateral_mint: Pubkey,
+    pub collateral_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoanRepaid {
+    pub loan_id: u64,
+    pub borrower: Pubkey,
+    pub lender: Pubkey,
+    pub principal: u64,
+    pub interest: u64,
+    pub origination_fee: u64,
+    pub prepayment_penalty: u64,
+    pub lender_interest: u64,    // 89% of interest
+    pub insurance_fee: u64,      // Dynamic premium → insurance pool
+    pub insurance_premium_bps: u16,
+    pub protocol_fee: u64,       // 1% of interest → treasury
+    pub total_repaid: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TradeExecuted {
+    pub loan_id: u64,
+    pub borrower: Pubkey,
+    pub target_program: Pubkey,
+    pub notional: u64,
+    pub trades_today: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoanLiquidated {
+    pub loan_id: u64,
+    pub borrower: Pubkey,
+    pub lender: Pubkey,
+    pub liquidator: Pubkey,
+    pub vault_balance_recovered: u64,
+    pub credit_clawed_back: u64,
+    pub positions_value: u64,
+    pub principal: u64,
+    pub health_factor_bps: u16,
+    pub is_past_due: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarginCallIssued {
+    pub loan_id: u64,
+    pub borrower: Pubkey,
+    pub caller: Pubkey,
+    pub health_factor_bps: u16,
+    pub warning_threshold_bps: u16,
+    pub deadline: i64,                    // Liquidation allowed after this if still unhealthy
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarginCallCleared {
+    pub loan_id: u64,
+    pub health_factor_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoanToppedUp {
+    pub loan_id: u64,
+    pub amount: u64,
+    pub vault_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OfferWarningThresholdUpdated {
+    pub lender: Pubkey,
+    pub offer: Pubkey,
+    pub warning_threshold_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidationAuctionStarted {
+    pub loan_id: u64,
+    pub debt_remaining: u64,
+    pub positions: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidationAuctionBid {
+    pub loan_id: u64,
+    pub bidder: Pubkey,
+    pub asset_mint: Pubkey,
+    pub amount: u64,
+    pub payment: u64,
+    pub price_bps: u16,                   // Fraction of the oracle price paid
+    pub to_lender: u64,
+    pub to_borrower: u64,
+    pub debt_remaining: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoanDefaulted {
+    pub loan_id: u64,
+    pub borrower: Pubkey,
+    pub lender: Pubkey,
+    pub principal: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InsuranceClaimed {
+    pub loan_id: u64,
+    pub lender: Pubkey,
+    pub borrower: Pubkey,
+    pub principal: u64,
+    pub payout: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DefaultedLoanRepaid {
+    pub loan_id: u64,
+    pub borrower: Pubkey,
+    pub lender: Pubkey,
+    pub amount: u64,
+    pub to_lender: u64,
+    pub to_insurance_pool: u64,
+    pub total_recovered: u64,
+    pub fully_recovered: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeRatesUpdated {
+    pub insurance_fee_bps: u16,
+    pub protocol_fee_bps: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ActionQueued {
+    pub action_id: u64,
+    pub action: GovernanceAction,
+    pub eta: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ActionExecuted {
+    pub action_id: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ActionCancelled {
+    pub action_id: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TimelockDelayUpdated {
+    pub delay_secs: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MultisigCreated {
+    pub multisig: Pubkey,
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MultisigActionProposed {
+    pub multisig: Pubkey,
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+    pub action: MultisigAction,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MultisigActionApproved {
+    pub multisig: Pubkey,
+    pub proposal_id: u64,
+    pub approver: Pubkey,
+    pub approvals: u8,
+    pub threshold: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MultisigActionExecuted {
+    pub multisig: Pubkey,
+    pub proposal_id: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MultisigActionCancelled {
+    pub multisig: Pubkey,
+    pub proposal_id: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseAuthorityUpdated {
+    pub pause_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseFlagsUpdated {
+    pub authority: Pubkey,
+    pub paused_flags: u8,
+    pub timestamp: i64,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct BorrowRequest {
-    pub borrower: Pubkey,
-    pub amount: u64,
-    pub max_rate_bps: u16,
-    pub duration_secs: u64,
-    pub is_active: bool,
-    pub created_at: i64,
-    pub bump: u8,
+#[event]
+pub struct CircuitBreakerConfigured {
+    pub window_secs: i64,
+    pub max_defaults: u32,
+    pub timestamp: i64,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct Loan {
-    pub id: u64,
-    pub lender: Pubkey,
-    pub borrower: Pubkey,
-    pub principal: u64,
-    pub rate_bps: u16,
-    pub start_time: i64,
-    pub end_time: i64,
-    pub status: LoanStatus,
-    pub vault: Pubkey,
-    pub liquidation_threshold_bps: u16,  // Copied from offer at loan creation
-    pub insurance_claimed: bool,         // Whether lender has claimed insurance for default
-    pub bump: u8,
+#[event]
+pub struct CircuitBreakerTripped {
+    pub defaults_in_window: u32,
+    pub window_start: i64,
+    pub paused_flags: u8,
+    pub timestamp: i64,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct BorrowerProfile {
-    pub owner: Pubkey,
-    pub reputation_score: u16,
-    pub loans_repaid: u32,
-    pub defaults: u32,
-    pub total_borrowed: u64,
-    pub created_at: i64,
-    pub bump: u8,
+#[event]
+pub struct OfferCollateralTermsUpdated {
+    pub lender: Pubkey,
+    pub offer: Pubkey,
+    pub collateral_mints: Vec<Pubkey>,
+    pub ltv_bps: u16,
+    pub timestamp: i64,
 }
 
-// ============================================================================
-// Enums
-// ============================================================================
+#[event]
+pub struct CollateralReleased {
+    pub loan_id: u64,
+    pub mint: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub seized: bool,                      // true when taken by the lender
+    pub timestamp: i64,
+}
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
-pub enum LoanStatus {
-    Open,       // Offer/request created, not yet matched
-    Active,     // Loan is active, borrower has funds
-    Repaid,     // Borrower repaid in full
-    Defaulted,  // Past due, marked as default
-    Liquidated, // Force-closed due to past due or unhealthy position
+#[event]
+pub struct RateModelUpdated {
+    pub base_rate_bps: u16,
+    pub slope1_bps: u16,
+    pub slope2_bps: u16,
+    pub optimal_utilization_bps: u16,
+    pub timestamp: i64,
 }
 
-impl Default for LoanStatus {
-    fn default() -> Self {
-        LoanStatus::Open
-    }
+#[event]
+pub struct OfferRepaymentTermsUpdated {
+    pub lender: Pubkey,
+    pub offer: Pubkey,
+    pub min_interest_secs: u64,
+    pub origination_fee_bps: u16,
+    pub prepayment_penalty_bps: u16,
+    pub timestamp: i64,
 }
 
-// ============================================================================
-// Events
-// ============================================================================
+#[event]
+pub struct OfferFlashTermsUpdated {
+    pub lender: Pubkey,
+    pub offer: Pubkey,
+    pub enabled: bool,
+    pub flash_fee_bps: u16,
+    pub timestamp: i64,
+}
 
 #[event]
-pub struct MarketInitialized {
-    pub admin: Pubkey,
-    pub insurance_pool: Pubkey,
-    pub treasury: Pubkey,
+pub struct FlashLoanBorrowed {
+    pub offer: Pubkey,
+    pub lender: Pubkey,
+    pub borrower: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct LendOfferPosted {
+pub struct FlashLoanRepaid {
+    pub offer: Pubkey,
     pub lender: Pubkey,
+    pub repayer: Pubkey,
     pub amount: u64,
-    pub min_rate_bps: u16,
-    pub max_duration_secs: u64,
-    pub liquidation_threshold_bps: u16,
+    pub lender_fee: u64,
+    pub insurance_fee: u64,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct LendOfferCancelled {
+pub struct OfferRateModeUpdated {
     pub lender: Pubkey,
+    pub offer: Pubkey,
+    pub rate_mode: RateMode,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct BorrowRequestPosted {
-    pub borrower: Pubkey,
-    pub amount: u64,
-    pub max_rate_bps: u16,
-    pub duration_secs: u64,
+pub struct PriceFeedUpdated {
+    pub mint: Pubkey,
+    pub oracle_program: Pubkey,
+    pub feed: Pubkey,
+    pub removed: bool,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct LoanCreated {
+pub struct LoanPositionUpdated {
+    pub loan_id: u64,
+    pub token_account: Pubkey,
+    pub mint: Pubkey,
+    pub removed: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoanSpendPolicyUpdated {
     pub loan_id: u64,
     pub lender: Pubkey,
+    pub spend_policy: SpendPolicy,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SessionKeyRegistered {
     pub borrower: Pubkey,
-    pub principal: u64,
-    pub rate_bps: u16,
-    pub end_time: i64,
-    pub liquidation_threshold_bps: u16,
+    pub session_key: Pubkey,
+    pub expires_at: i64,
+    pub allowed_actions: u8,
+    pub spend_cap: u64,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct LoanRepaid {
-    pub loan_id: u64,
+pub struct SessionKeyRevoked {
     pub borrower: Pubkey,
-    pub lender: Pubkey,
-    pub principal: u64,
-    pub interest: u64,
-    pub lender_interest: u64,    // 89% of interest
-    pub insurance_fee: u64,      // 10% of interest → insurance pool
-    pub protocol_fee: u64,       // 1% of interest → treasury
-    pub total_repaid: u64,
+    pub session_key: Pubkey,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct TradeExecuted {
+pub struct MerchantUpdated {
+    pub market: Pubkey,
+    pub approver: Pubkey,
+    pub token_account: Pubkey,
+    pub approved: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoanMerchantsUpdated {
     pub loan_id: u64,
-    pub borrower: Pubkey,
-    pub target_program: Pubkey,
+    pub lender: Pubkey,
+    pub merchants: Vec<Pubkey>,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct LoanLiquidated {
+pub struct MerchantPayment {
     pub loan_id: u64,
     pub borrower: Pubkey,
-    pub lender: Pubkey,
-    pub liquidator: Pubkey,
-    pub vault_balance_recovered: u64,
-    pub principal: u64,
-    pub health_factor_bps: u16,
-    pub is_past_due: bool,
+    pub merchant: Pubkey,
+    pub merchant_owner: Pubkey,
+    pub amount: u64,
+    pub received: u64,
+    pub memo: String,
+    pub total_spent: u64,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct LoanDefaulted {
+pub struct CreditWrapperInitialized {
+    pub market: Pubkey,
+    pub credit_mint: Pubkey,
+    pub reserve: Pubkey,
+    pub transfer_hook_program: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RedemptionAddressUpdated {
+    pub wrapper: Pubkey,
+    pub address: Pubkey,
+    pub approved: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CreditWrapped {
+    pub wrapper: Pubkey,
+    pub holder: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CreditUnwrapped {
+    pub wrapper: Pubkey,
+    pub holder: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LoanCreditDisbursed {
     pub loan_id: u64,
     pub borrower: Pubkey,
-    pub lender: Pubkey,
-    pub principal: u64,
+    pub amount: u64,
+    pub credit_outstanding: u64,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct InsuranceClaimed {
+pub struct LoanCreditReturned {
     pub loan_id: u64,
-    pub lender: Pubkey,
     pub borrower: Pubkey,
-    pub principal: u64,
-    pub payout: u64,
+    pub amount: u64,
+    pub credit_outstanding: u64,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct FeeRatesUpdated {
-    pub insurance_fee_bps: u16,
-    pub protocol_fee_bps: u16,
+pub struct PremiumCurveUpdated {
+    pub base_premium_bps: u16,
+    pub min_premium_bps: u16,
+    pub max_premium_bps: u16,
+    pub target_solvency_bps: u32,
+    pub tier_discount_bps: u16,
+    pub solvency_ratio_bps: u32,
     pub timestamp: i64,
 }
 
@@ -1164,4 +6649,726 @@ pub enum CreditMarketError {
     InvalidTreasury,
     #[msg("Invalid fee rate - exceeds maximum allowed")]
     InvalidFeeRate,
+    #[msg("Nothing left to recover on this loan")]
+    NothingToRecover,
+    #[msg("Invalid insurance premium curve parameters")]
+    InvalidPremiumCurve,
+    #[msg("Instruction is paused")]
+    InstructionPaused,
+    #[msg("Invalid pause flags")]
+    InvalidPauseFlags,
+    #[msg("Timelock is active - queue this change with queue_action")]
+    TimelockActive,
+    #[msg("Timelock has not elapsed for this action")]
+    TimelockNotElapsed,
+    #[msg("Invalid timelock delay")]
+    InvalidTimelockDelay,
+    #[msg("Whitelist is full")]
+    WhitelistFull,
+    #[msg("Invalid multisig signers or threshold")]
+    InvalidMultisig,
+    #[msg("Signer is not part of the multisig")]
+    NotMultisigSigner,
+    #[msg("Signer has already approved this proposal")]
+    AlreadyApproved,
+    #[msg("Proposal has not reached the approval threshold")]
+    ThresholdNotMet,
+    #[msg("Proposal was created under a previous signer set")]
+    StaleProposal,
+    #[msg("Token account mint does not match the market mint")]
+    MintMismatch,
+    #[msg("Account belongs to a different market")]
+    MarketMismatch,
+    #[msg("Recipient received less than the required amount")]
+    TransferAmountMismatch,
+    #[msg("Loan has credit outstanding - return it with unwrap_to_loan")]
+    CreditOutstanding,
+    #[msg("Credit accounts are required for a loan with credit outstanding")]
+    CreditAccountsMissing,
+    #[msg("Credit account does not match the loan's credit account")]
+    CreditAccountMismatch,
+    #[msg("Invalid credit reserve address")]
+    InvalidCreditReserve,
+    #[msg("Invalid transfer hook program")]
+    InvalidTransferHookProgram,
+    #[msg("Merchant label too long")]
+    LabelTooLong,
+    #[msg("Payment memo too long")]
+    MemoTooLong,
+    #[msg("Too many merchants for one loan")]
+    TooManyMerchants,
+    #[msg("Merchant is not approved by the admin or the loan's lender")]
+    MerchantNotApproved,
+    #[msg("Merchant is not on this loan's allowlist")]
+    MerchantNotAllowedForLoan,
+    #[msg("Invalid session key")]
+    InvalidSessionKey,
+    #[msg("Session key has expired")]
+    SessionKeyExpired,
+    #[msg("Session key is not allowed to perform this action")]
+    SessionActionNotAllowed,
+    #[msg("Session key spend cap exceeded")]
+    SessionSpendCapExceeded,
+    #[msg("Invalid spend policy")]
+    InvalidSpendPolicy,
+    #[msg("Trade notional exceeds the loan's per-trade limit")]
+    TradeNotionalExceeded,
+    #[msg("Loan has reached its daily trade limit")]
+    DailyTradeLimitExceeded,
+    #[msg("Program is not allowed by the loan's spend policy")]
+    ProgramNotAllowedByPolicy,
+    #[msg("Trade exceeds the loan's maximum fraction of the vault")]
+    TradeExceedsVaultFraction,
+    #[msg("Too many price feeds")]
+    PriceFeedsFull,
+    #[msg("No price feed configured for this mint")]
+    PriceFeedNotConfigured,
+    #[msg("Invalid price feed account")]
+    InvalidPriceFeed,
+    #[msg("Oracle price is not available")]
+    InvalidOraclePrice,
+    #[msg("Oracle price is stale")]
+    OraclePriceStale,
+    #[msg("Oracle confidence interval is too wide")]
+    OracleConfidenceTooWide,
+    #[msg("Loan tracks too many positions")]
+    TooManyPositions,
+    #[msg("Position is already tracked")]
+    PositionAlreadyTracked,
+    #[msg("Position still holds tokens")]
+    PositionNotEmpty,
+    #[msg("Invalid loan position")]
+    InvalidPosition,
+    #[msg("Missing position accounts")]
+    MissingPositionAccounts,
+    #[msg("Invalid collateral terms")]
+    InvalidCollateralTerms,
+    #[msg("Offer does not accept this collateral")]
+    CollateralNotAccepted,
+    #[msg("Collateral accounts are required for this loan")]
+    CollateralAccountsMissing,
+    #[msg("Invalid collateral vault")]
+    InvalidCollateralVault,
+    #[msg("Loan amount exceeds the borrower's credit limit")]
+    CreditLimitExceeded,
+    #[msg("No liquidation auction is running for this loan")]
+    AuctionNotActive,
+    #[msg("A liquidation auction is still running for this loan")]
+    AuctionActive,
+    #[msg("Auction payment exceeds the bidder's maximum")]
+    AuctionSlippageExceeded,
+    #[msg("Invalid warning threshold")]
+    InvalidWarningThreshold,
+    #[msg("Loan health is above the warning threshold")]
+    LoanNotAtRisk,
+    #[msg("Loan health is still below the warning threshold")]
+    LoanStillAtRisk,
+    #[msg("Loan is not under a margin call")]
+    NoMarginCall,
+    #[msg("Unhealthy loans can only be liquidated after a margin call's cure window")]
+    MarginCallPending,
+    #[msg("Invalid timestamp")]
+    InvalidTimestamp,
+    #[msg("Repayment exceeds the maximum total")]
+    RepaymentExceedsMax,
+    #[msg("Invalid rate model")]
+    InvalidRateModel,
+    #[msg("Invalid repayment terms")]
+    InvalidRepaymentTerms,
+    #[msg("Invalid flash loan terms")]
+    InvalidFlashTerms,
+    #[msg("Offer does not allow flash loans")]
+    FlashLoansDisabled,
+    #[msg("Offer has a flash loan outstanding")]
+    FlashLoanActive,
+    #[msg("Offer has no flash loan outstanding")]
+    FlashLoanNotActive,
+    #[msg("No flash_repay for this offer later in the transaction")]
+    FlashRepayMissing,
+    #[msg("flash_borrow cannot be called through CPI")]
+    FlashLoanCpiNotAllowed,
+    #[msg("Offer terms differ from what the borrower expected")]
+    OfferTermsChanged,
+    #[msg("Proposal approves an admin instruction, call it with the proposal instead")]
+    ApprovalOnlyAction,
+    #[msg("Approved proposal is for a different action")]
+    ApprovalActionMismatch,
+    #[msg("Circuit breaker allows too many defaults per window")]
+    InvalidCircuitBreaker,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_USDC: u64 = 1_000_000;
+
+    /// Market as `initialize` leaves it
+    fn test_global_state() -> GlobalState {
+        GlobalState {
+            admin: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            next_loan_id: 1,
+            fee_bps: PROTOCOL_FEE_BPS as u16,
+            insurance_fee_bps: INSURANCE_FEE_BPS as u16,
+            insurance_pool: Pubkey::new_unique(),
+            treasury: Pubkey::new_unique(),
+            total_insurance_collected: 0,
+            total_insurance_claimed: 0,
+            solvency_ratio_bps: MAX_SOLVENCY_RATIO_BPS,
+            min_premium_bps: DEFAULT_MIN_PREMIUM_BPS,
+            max_premium_bps: DEFAULT_MAX_PREMIUM_BPS,
+            target_solvency_bps: DEFAULT_TARGET_SOLVENCY_BPS,
+            tier_discount_bps: DEFAULT_TIER_DISCOUNT_BPS,
+            pending_admin: Pubkey::default(),
+            timelock_delay_secs: DEFAULT_TIMELOCK_DELAY_SECS,
+            next_action_id: 1,
+            pause_authority: Pubkey::default(),
+            paused_flags: 0,
+            breaker_window_secs: 0,
+            breaker_max_defaults: 0,
+            breaker_recent_defaults: vec![],
+            whitelisted_programs: vec![],
+            price_feeds: vec![],
+            rate_model: RateModel {
+                base_rate_bps: DEFAULT_BASE_RATE_BPS,
+                slope1_bps: DEFAULT_RATE_SLOPE1_BPS,
+                slope2_bps: DEFAULT_RATE_SLOPE2_BPS,
+                optimal_utilization_bps: DEFAULT_OPTIMAL_UTILIZATION_BPS,
+            },
+            borrow_index: BORROW_INDEX_ONE,
+            index_updated_at: 0,
+            total_offered: 0,
+            total_borrowed: 0,
+            bump: 255,
+        }
+    }
+
+    /// Closed-form principal * ((1 + r / n)^t - 1) with n = seconds per year
+    fn closed_form_interest(principal: u64, rate_bps: u16, duration_secs: u64) -> f64 {
+        let rate_per_sec = rate_bps as f64 / 10000.0 / SECONDS_PER_YEAR as f64;
+        principal as f64 * (duration_secs as f64 * rate_per_sec.ln_1p()).exp_m1()
+    }
+
+    #[test]
+    fn test_wad_pow_up() {
+        assert_eq!(wad_pow_up(2 * WAD, 0).unwrap(), WAD);
+        assert_eq!(wad_pow_up(2 * WAD, 10).unwrap(), 1024 * WAD);
+        assert_eq!(wad_pow_up(WAD + WAD / 2, 2).unwrap(), 2 * WAD + WAD / 4);
+        // 1/3 can't be exact, rounding is upward
+        let third = WAD.div_ceil(3);
+        assert!(wad_pow_up(third, 3).unwrap() * 27 >= WAD);
+    }
+
+    #[test]
+    fn test_compound_interest_matches_closed_form() {
+        // Within 1e-8 of the interest, or 2 base units, and never below the closed form
+        let cases: [(u64, u16, u64); 6] = [
+            (ONE_USDC, 1000, 60),
+            (1_000 * ONE_USDC, 500, 24 * 60 * 60),
+            (50_000 * ONE_USDC, 1200, 30 * 24 * 60 * 60),
+            (1_000_000 * ONE_USDC, 800, SECONDS_PER_YEAR),
+            (10_000 * ONE_USDC, 10000, 2 * SECONDS_PER_YEAR),
+            (7 * ONE_USDC, 1, 12345),
+        ];
+        for (principal, rate_bps, duration_secs) in cases {
+            let interest = calculate_compound_interest(principal, rate_bps, duration_secs).unwrap();
+            let expected = closed_form_interest(principal, rate_bps, duration_secs);
+            let tolerance = (expected * 1e-8).max(2.0);
+            assert!(
+                (interest as f64 - expected).abs() <= tolerance,
+                "{principal} at {rate_bps} bps for {duration_secs}s: {interest} vs {expected}"
+            );
+            assert!(interest as f64 >= expected.floor());
+        }
+    }
+
+    #[test]
+    fn test_compound_interest_rounds_for_lender() {
+        // A one-second loan still pays interest, simple interest truncates it to zero
+        assert_eq!(calculate_interest(ONE_USDC, 500, 1).unwrap(), 0);
+        assert_eq!(calculate_compound_interest(ONE_USDC, 500, 1).unwrap(), 1);
+        assert_eq!(calculate_compound_interest(ONE_USDC, 0, 1000).unwrap(), 0);
+        assert_eq!(calculate_compound_interest(ONE_USDC, 500, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_compound_exceeds_simple_interest() {
+        let principal = 100_000 * ONE_USDC;
+        let simple = calculate_interest(principal, 1000, SECONDS_PER_YEAR).unwrap();
+        let compound = calculate_compound_interest(principal, 1000, SECONDS_PER_YEAR).unwrap();
+        assert_eq!(simple, 10_000 * ONE_USDC);
+        // e^0.1 - 1 = 10.517%
+        assert!(compound > simple);
+        assert!((compound as f64 / principal as f64 - 0.10517).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_solvency_ratio() {
+        // Nothing claimed yet counts as fully solvent
+        assert_eq!(calculate_solvency_ratio_bps(0, 0).unwrap(), MAX_SOLVENCY_RATIO_BPS);
+        assert_eq!(calculate_solvency_ratio_bps(ONE_USDC, 0).unwrap(), MAX_SOLVENCY_RATIO_BPS);
+        assert_eq!(calculate_solvency_ratio_bps(3 * ONE_USDC, ONE_USDC).unwrap(), 30000);
+        assert_eq!(calculate_solvency_ratio_bps(ONE_USDC, 4 * ONE_USDC).unwrap(), 2500);
+        assert_eq!(calculate_solvency_ratio_bps(0, ONE_USDC).unwrap(), 0);
+        // Capped at 10x
+        assert_eq!(
+            calculate_solvency_ratio_bps(11 * ONE_USDC, ONE_USDC).unwrap(),
+            MAX_SOLVENCY_RATIO_BPS
+        );
+        assert_eq!(
+            calculate_solvency_ratio_bps(u64::MAX, 1).unwrap(),
+            MAX_SOLVENCY_RATIO_BPS
+        );
+    }
+
+    #[test]
+    fn test_insurance_premium_follows_solvency() {
+        let mut global_state = test_global_state();
+
+        // At the target solvency the premium is the base insurance fee
+        global_state.solvency_ratio_bps = DEFAULT_TARGET_SOLVENCY_BPS;
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 1).unwrap(), 1000);
+        // Half the target doubles it, right at the ceiling
+        global_state.solvency_ratio_bps = DEFAULT_TARGET_SOLVENCY_BPS / 2;
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 1).unwrap(), 2000);
+        // Less solvent than that stays at the ceiling, an empty pool included
+        global_state.solvency_ratio_bps = 1000;
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 1).unwrap(), 2000);
+        global_state.solvency_ratio_bps = 0;
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 1).unwrap(), 2000);
+        // A well-funded pool bottoms out at the floor
+        global_state.solvency_ratio_bps = 30000;
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 1).unwrap(), 666);
+        global_state.solvency_ratio_bps = MAX_SOLVENCY_RATIO_BPS;
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 1).unwrap(), 500);
+    }
+
+    #[test]
+    fn test_insurance_premium_tier_discount() {
+        let mut global_state = test_global_state();
+        global_state.solvency_ratio_bps = DEFAULT_TARGET_SOLVENCY_BPS;
+
+        // 5% off per tier above 1, tier 0 is treated like tier 1
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 0).unwrap(), 1000);
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 2).unwrap(), 950);
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 5).unwrap(), 800);
+
+        // The largest allowed discount takes tier 5 to zero, never below
+        global_state.tier_discount_bps = 2500;
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 5).unwrap(), 0);
+        global_state.tier_discount_bps = u16::MAX;
+        assert_eq!(calculate_insurance_premium_bps(&global_state, 5).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_premium_curve_update_bounds() {
+        let curve = |min, max, target, discount| GovernanceAction::UpdatePremiumCurve {
+            min_premium_bps: min,
+            max_premium_bps: max,
+            target_solvency_bps: target,
+            tier_discount_bps: discount,
+        };
+
+        assert!(validate_governance_action(&curve(Some(0), Some(2000), Some(1), Some(2500))).is_ok());
+        assert!(validate_governance_action(&curve(None, Some(2001), None, None)).is_err());
+        assert!(validate_governance_action(&curve(Some(600), Some(500), None, None)).is_err());
+        assert!(validate_governance_action(&curve(None, None, Some(0), None)).is_err());
+        assert!(
+            validate_governance_action(&curve(None, None, Some(MAX_SOLVENCY_RATIO_BPS + 1), None))
+                .is_err()
+        );
+        assert!(validate_governance_action(&curve(None, None, None, Some(2501))).is_err());
+
+        // A lone min or max is checked against the current curve when applied
+        let mut global_state = test_global_state();
+        let raise_min = curve(Some(DEFAULT_MAX_PREMIUM_BPS + 1), None, None, None);
+        assert!(validate_governance_action(&raise_min).is_ok());
+        assert!(apply_governance_action(&mut global_state, &raise_min, 0).is_err());
+
+        let update = curve(Some(100), Some(1500), Some(15000), Some(0));
+        apply_governance_action(&mut global_state, &update, 0).unwrap();
+        assert_eq!(global_state.min_premium_bps, 100);
+        assert_eq!(global_state.max_premium_bps, 1500);
+        assert_eq!(global_state.target_solvency_bps, 15000);
+        assert_eq!(global_state.tier_discount_bps, 0);
+    }
+
+    /// Pyth v2 price account with the given aggregate price
+    fn pyth_price_data(price: i64, conf: u64, expo: i32, publish_time: i64, status: u32) -> Vec<u8> {
+        let mut data = vec![0u8; PYTH_MIN_ACCOUNT_LEN];
+        data[PYTH_MAGIC_OFFSET..PYTH_MAGIC_OFFSET + 4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[PYTH_ACCOUNT_TYPE_OFFSET..PYTH_ACCOUNT_TYPE_OFFSET + 4]
+            .copy_from_slice(&PYTH_ACCOUNT_TYPE_PRICE.to_le_bytes());
+        data[PYTH_EXPO_OFFSET..PYTH_EXPO_OFFSET + 4].copy_from_slice(&expo.to_le_bytes());
+        data[PYTH_TIMESTAMP_OFFSET..PYTH_TIMESTAMP_OFFSET + 8]
+            .copy_from_slice(&publish_time.to_le_bytes());
+        data[PYTH_AGG_PRICE_OFFSET..PYTH_AGG_PRICE_OFFSET + 8].copy_from_slice(&price.to_le_bytes());
+        data[PYTH_AGG_CONF_OFFSET..PYTH_AGG_CONF_OFFSET + 8].copy_from_slice(&conf.to_le_bytes());
+        data[PYTH_AGG_STATUS_OFFSET..PYTH_AGG_STATUS_OFFSET + 4]
+            .copy_from_slice(&status.to_le_bytes());
+        data
+    }
+
+    fn load_price(data: &mut [u8]) -> Result<OraclePrice> {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut lamports = 0;
+        let info = AccountInfo::new(&key, false, false, &mut lamports, data, &owner, false, 0);
+        OraclePrice::load(&info)
+    }
+
+    fn test_price_feed() -> PriceFeed {
+        PriceFeed {
+            mint: Pubkey::new_unique(),
+            oracle_program: Pubkey::new_unique(),
+            feed: Pubkey::new_unique(),
+            max_staleness_secs: 60,
+            max_confidence_bps: 100,
+        }
+    }
+
+    #[test]
+    fn test_value_in_quote() {
+        // 1 SOL (9 decimals) at $150 with a negative expo, in 6-decimal USDC
+        assert_eq!(value_in_quote(1_000_000_000, 15_000_000_000, -8, 9, 6).unwrap(), 150 * ONE_USDC);
+        assert_eq!(value_in_quote(2_500_000, 15_000_000_000, -8, 9, 6).unwrap(), 375_000);
+        // Zero and positive scales multiply instead of dividing
+        assert_eq!(value_in_quote(7, 3, 0, 0, 0).unwrap(), 21);
+        assert_eq!(value_in_quote(3, 5, 0, 0, 6).unwrap(), 15 * ONE_USDC);
+        assert_eq!(value_in_quote(4, 2, 2, 6, 6).unwrap(), 800);
+        // Dust rounds down to nothing
+        assert_eq!(value_in_quote(1, 15_000_000_000, -8, 9, 6).unwrap(), 0);
+        // Results past u64 and unrepresentable scales are errors
+        assert!(value_in_quote(u64::MAX, u64::MAX, 0, 0, 0).is_err());
+        assert!(value_in_quote(u64::MAX, 10, 0, 0, 6).is_err());
+        assert!(value_in_quote(1, 1, i32::MAX, 0, 6).is_err());
+        assert!(value_in_quote(1, 1, 40, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_oracle_price_load() {
+        let mut data = pyth_price_data(15_000_000_000, 5_000_000, -8, 1_000, PYTH_STATUS_TRADING);
+        let price = load_price(&mut data).unwrap();
+        assert_eq!(price.price, 15_000_000_000);
+        assert_eq!(price.conf, 5_000_000);
+        assert_eq!(price.expo, -8);
+        assert_eq!(price.publish_time, 1_000);
+
+        // Anything that isn't a trading price account is rejected
+        let mut halted = pyth_price_data(15_000_000_000, 0, -8, 1_000, 0);
+        assert!(load_price(&mut halted).is_err());
+        let mut bad_magic = data.clone();
+        bad_magic[PYTH_MAGIC_OFFSET] ^= 1;
+        assert!(load_price(&mut bad_magic).is_err());
+        let mut not_price = data.clone();
+        not_price[PYTH_ACCOUNT_TYPE_OFFSET] = 2;
+        assert!(load_price(&mut not_price).is_err());
+        let mut short = data[..PYTH_MIN_ACCOUNT_LEN - 1].to_vec();
+        assert!(load_price(&mut short).is_err());
+    }
+
+    #[test]
+    fn test_conservative_price() {
+        let feed = test_price_feed();
+        let price = |price, conf, publish_time| OraclePrice { price, conf, expo: -8, publish_time };
+
+        // Price less its confidence, 0.5% wide against a 1% limit
+        assert_eq!(price(10_000, 50, 1_000).conservative_price(&feed, 1_000).unwrap(), 9_950);
+        assert_eq!(price(10_000, 100, 1_000).conservative_price(&feed, 1_060).unwrap(), 9_900);
+        // Too wide, too old, non-positive or from the future
+        assert!(price(10_000, 101, 1_000).conservative_price(&feed, 1_000).is_err());
+        assert!(price(10_000, 0, 1_000).conservative_price(&feed, 1_061).is_err());
+        assert!(price(0, 0, 1_000).conservative_price(&feed, 1_000).is_err());
+        assert!(price(-10_000, 0, 1_000).conservative_price(&feed, 1_000).is_err());
+        assert!(price(10_000, 0, 1_001).conservative_price(&feed, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_auction_price_decay() {
+        let start = 1_000_000;
+        let duration = AUCTION_DURATION_SECS;
+        assert_eq!(auction_price_bps(start, start).unwrap(), AUCTION_START_PRICE_BPS);
+        // Linear, truncating towards the start price
+        assert_eq!(auction_price_bps(start, start + 1).unwrap(), AUCTION_START_PRICE_BPS);
+        assert_eq!(auction_price_bps(start, start + 9).unwrap(), 10_995);
+        assert_eq!(auction_price_bps(start, start + duration / 2).unwrap(), 9_000);
+        assert_eq!(auction_price_bps(start, start + duration - 1).unwrap(), 7_001);
+        // Holds at the floor from the end of the decay on
+        assert_eq!(auction_price_bps(start, start + duration).unwrap(), AUCTION_FLOOR_PRICE_BPS);
+        assert_eq!(auction_price_bps(start, i64::MAX).unwrap(), AUCTION_FLOOR_PRICE_BPS);
+        // A clock behind the start is treated as the start
+        assert_eq!(auction_price_bps(start, start - 10).unwrap(), AUCTION_START_PRICE_BPS);
+        assert!(auction_price_bps(i64::MAX, -2).is_err());
+    }
+
+    #[test]
+    fn test_utilization() {
+        let mut global_state = test_global_state();
+        assert_eq!(utilization_bps(&global_state), 0);
+        global_state.total_offered = 1_000 * ONE_USDC;
+        assert_eq!(utilization_bps(&global_state), 0);
+        global_state.total_borrowed = 4_000 * ONE_USDC;
+        assert_eq!(utilization_bps(&global_state), 8000);
+        global_state.total_offered = 0;
+        assert_eq!(utilization_bps(&global_state), 10000);
+    }
+
+    #[test]
+    fn test_variable_rate_curve() {
+        let model = test_global_state().rate_model;
+        assert_eq!(variable_rate_bps(&model, 0), 200);
+        assert_eq!(variable_rate_bps(&model, 4000), 400);
+        // The kink: slope1 is fully applied at optimal, slope2 only past it
+        assert_eq!(variable_rate_bps(&model, 8000), 600);
+        assert_eq!(variable_rate_bps(&model, 8001), 603);
+        assert_eq!(variable_rate_bps(&model, 9000), 4350);
+        assert_eq!(variable_rate_bps(&model, 10000), 8100);
+
+        // Optimal at full utilization never reaches slope2
+        let flat = RateModel { optimal_utilization_bps: 10000, ..model.clone() };
+        assert_eq!(variable_rate_bps(&flat, 5000), 400);
+        assert_eq!(variable_rate_bps(&flat, 10000), 600);
+        // The largest curve governance accepts
+        let steep = RateModel {
+            base_rate_bps: 0,
+            slope1_bps: 0,
+            slope2_bps: MAX_VARIABLE_RATE_BPS as u16,
+            optimal_utilization_bps: 1,
+        };
+        assert!(validate_governance_action(&GovernanceAction::SetRateModel { model: steep.clone() })
+            .is_ok());
+        assert_eq!(variable_rate_bps(&steep, 10000), MAX_VARIABLE_RATE_BPS as u64);
+    }
+
+    #[test]
+    fn test_borrow_index_growth() {
+        let mut global_state = test_global_state();
+        global_state.index_updated_at = 1_000;
+
+        // No time, or a clock behind the last update, leaves the index alone
+        assert_eq!(borrow_index_at(&global_state, 1_000).unwrap(), BORROW_INDEX_ONE);
+        assert_eq!(borrow_index_at(&global_state, 0).unwrap(), BORROW_INDEX_ONE);
+        // A year at the 2% base rate, exactly
+        let year_later = 1_000 + SECONDS_PER_YEAR as i64;
+        assert_eq!(
+            borrow_index_at(&global_state, year_later).unwrap(),
+            BORROW_INDEX_ONE + BORROW_INDEX_ONE / 50
+        );
+        // A single second still grows the index, rounded up
+        assert_eq!(borrow_index_at(&global_state, 1_001).unwrap(), BORROW_INDEX_ONE + 634_195_840);
+
+        // Growth follows utilization: 80% borrowed is 6%
+        global_state.total_borrowed = 800 * ONE_USDC;
+        global_state.total_offered = 200 * ONE_USDC;
+        assert_eq!(
+            borrow_index_at(&global_state, year_later).unwrap(),
+            BORROW_INDEX_ONE + BORROW_INDEX_ONE * 6 / 100
+        );
+
+        // Accrual moves the checkpoint but never backwards
+        accrue_borrow_index(&mut global_state, year_later).unwrap();
+        assert_eq!(global_state.index_updated_at, year_later);
+        accrue_borrow_index(&mut global_state, 1_000).unwrap();
+        assert_eq!(global_state.index_updated_at, year_later);
+        assert_eq!(global_state.borrow_index, BORROW_INDEX_ONE + BORROW_INDEX_ONE * 6 / 100);
+    }
+
+    /// Active fixed-rate loan of `principal` at 10% for a year from t = 0, no repayment terms
+    fn test_loan(principal: u64) -> Loan {
+        Loan {
+            id: 1,
+            lender: Pubkey::new_unique(),
+            borrower: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            principal,
+            rate_bps: 1000,
+            start_time: 0,
+            end_time: SECONDS_PER_YEAR as i64,
+            status: LoanStatus::Active,
+            vault: Pubkey::new_unique(),
+            liquidation_threshold_bps: 11000,
+            warning_threshold_bps: 12000,
+            margin_call_deadline: 0,
+            insurance_claimed: false,
+            insurance_payout: 0,
+            recovered_amount: 0,
+            credit_outstanding: 0,
+            credit_account: Pubkey::default(),
+            total_spent: 0,
+            allowed_merchants: vec![],
+            spend_policy: SpendPolicy::default(),
+            trade_day_start: 0,
+            trades_today: 0,
+            positions: vec![],
+            collateral_mint: Pubkey::default(),
+            collateral_vault: Pubkey::default(),
+            collateral_amount: 0,
+            auction_start: 0,
+            auction_debt_remaining: 0,
+            rate_mode: RateMode::Fixed,
+            entry_borrow_index: BORROW_INDEX_ONE,
+            exit_borrow_index: 0,
+            min_interest_secs: 0,
+            origination_fee_bps: 0,
+            prepayment_penalty_bps: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn test_repayment_at_maturity() {
+        let global_state = test_global_state();
+        let loan = test_loan(1_000 * ONE_USDC);
+
+        let quote = calculate_repayment_split(&loan, &global_state, 1, loan.end_time).unwrap();
+        assert_eq!(quote.interest, 100 * ONE_USDC);
+        assert_eq!(quote.prepayment_penalty, 0);
+        // Fully solvent pool: 5% floor premium, 1% protocol, the rest to the lender
+        assert_eq!(quote.insurance_premium_bps, DEFAULT_MIN_PREMIUM_BPS);
+        assert_eq!(quote.insurance_fee, 5 * ONE_USDC);
+        assert_eq!(quote.protocol_fee, ONE_USDC);
+        assert_eq!(quote.lender_interest, 94 * ONE_USDC);
+        assert_eq!(quote.total, 1_100 * ONE_USDC);
+        assert_eq!(quote.total_charged, quote.total);
+
+        // Late repayment keeps accruing
+        let late = calculate_repayment_split(&loan, &global_state, 1, loan.end_time * 2).unwrap();
+        assert_eq!(late.interest, 200 * ONE_USDC);
+        // Higher tiers get the premium discount
+        let tier_5 = calculate_repayment_split(&loan, &global_state, 5, loan.end_time).unwrap();
+        assert_eq!(tier_5.insurance_premium_bps, 400);
+        assert_eq!(tier_5.insurance_fee, 4 * ONE_USDC);
+        assert_eq!(tier_5.lender_interest, 95 * ONE_USDC);
+    }
+
+    #[test]
+    fn test_repayment_min_interest() {
+        let global_state = test_global_state();
+        let mut loan = test_loan(1_000 * ONE_USDC);
+        let month = 30 * 24 * 60 * 60;
+        loan.min_interest_secs = month;
+        let month_interest = calculate_interest(loan.principal, loan.rate_bps, month).unwrap();
+
+        // Repaying early still pays the minimum period
+        let day = calculate_repayment_split(&loan, &global_state, 1, 24 * 60 * 60).unwrap();
+        assert_eq!(day.interest, month_interest);
+        let at_start = calculate_repayment_split(&loan, &global_state, 1, 0).unwrap();
+        assert_eq!(at_start.interest, month_interest);
+        let at_minimum = calculate_repayment_split(&loan, &global_state, 1, month as i64).unwrap();
+        assert_eq!(at_minimum.interest, month_interest);
+        // Past it, interest is whatever accrued
+        let later = calculate_repayment_split(&loan, &global_state, 1, 2 * month as i64).unwrap();
+        assert_eq!(
+            later.interest,
+            calculate_interest(loan.principal, loan.rate_bps, 2 * month).unwrap()
+        );
+
+        // Variable-rate loans closed out pay the minimum from the frozen index
+        loan.rate_mode = RateMode::Variable;
+        loan.exit_borrow_index = BORROW_INDEX_ONE + BORROW_INDEX_ONE / 20;
+        let variable = calculate_repayment_split(&loan, &global_state, 1, 0).unwrap();
+        assert_eq!(variable.interest, 50 * ONE_USDC);
+    }
+
+    #[test]
+    fn test_repayment_origination_and_prepayment() {
+        let global_state = test_global_state();
+        let mut loan = test_loan(1_000 * ONE_USDC);
+        loan.origination_fee_bps = 100;
+        loan.prepayment_penalty_bps = 200;
+
+        // The penalty applies up to, but not at, end_time
+        let early = calculate_repayment_split(&loan, &global_state, 1, loan.end_time - 1).unwrap();
+        assert_eq!(early.origination_fee, 10 * ONE_USDC);
+        assert_eq!(early.prepayment_penalty, 20 * ONE_USDC);
+        let at_end = calculate_repayment_split(&loan, &global_state, 1, loan.end_time).unwrap();
+        assert_eq!(at_end.origination_fee, 10 * ONE_USDC);
+        assert_eq!(at_end.prepayment_penalty, 0);
+
+        // Fees are split like interest
+        assert_eq!(at_end.insurance_fee, 5_500_000);
+        assert_eq!(at_end.protocol_fee, 1_100_000);
+        assert_eq!(at_end.lender_interest, 103_400_000);
+        assert_eq!(at_end.total, 1_110 * ONE_USDC);
+        assert_eq!(
+            at_end.lender_interest + at_end.insurance_fee + at_end.protocol_fee,
+            at_end.interest + at_end.origination_fee
+        );
+
+        // Both fees round up in the lender's favour
+        let mut dust = test_loan(1);
+        dust.origination_fee_bps = 1;
+        dust.prepayment_penalty_bps = 1;
+        let quote = calculate_repayment_split(&dust, &global_state, 1, 0).unwrap();
+        assert_eq!(quote.origination_fee, 1);
+        assert_eq!(quote.prepayment_penalty, 1);
+        assert_eq!(quote.total, 3);
+    }
+
+    #[test]
+    fn test_circuit_breaker_rolling_window() {
+        let mut global_state = test_global_state();
+        global_state.breaker_window_secs = 100;
+        global_state.breaker_max_defaults = 2;
+
+        record_default_for_breaker(&mut global_state, 1_000).unwrap();
+        record_default_for_breaker(&mut global_state, 1_050).unwrap();
+        assert_eq!(global_state.paused_flags, 0);
+        // The first default has left the window by the time the third arrives
+        record_default_for_breaker(&mut global_state, 1_100).unwrap();
+        assert_eq!(global_state.paused_flags, 0);
+        // Three within 100 seconds trips it, though a fixed window would have reset at 1_100
+        record_default_for_breaker(&mut global_state, 1_149).unwrap();
+        assert_eq!(global_state.paused_flags, PAUSE_ALL);
+        assert_eq!(global_state.breaker_recent_defaults, vec![1_050, 1_100, 1_149]);
+    }
+
+    #[test]
+    fn test_circuit_breaker_burst_across_boundary() {
+        // max_defaults at the end of one window and more at the start of the next
+        let mut global_state = test_global_state();
+        global_state.breaker_window_secs = 100;
+        global_state.breaker_max_defaults = 2;
+        record_default_for_breaker(&mut global_state, 98).unwrap();
+        record_default_for_breaker(&mut global_state, 99).unwrap();
+        assert_eq!(global_state.paused_flags, 0);
+        record_default_for_breaker(&mut global_state, 100).unwrap();
+        assert_eq!(global_state.paused_flags, PAUSE_ALL);
+
+        // Spread out, they never trip it, and history stays bounded
+        let mut global_state = test_global_state();
+        global_state.breaker_window_secs = 100;
+        global_state.breaker_max_defaults = 2;
+        for t in (0..2_000).step_by(50) {
+            record_default_for_breaker(&mut global_state, t).unwrap();
+        }
+        assert_eq!(global_state.paused_flags, 0);
+        assert_eq!(global_state.breaker_recent_defaults.len(), 3);
+
+        // A disabled breaker records nothing
+        let mut global_state = test_global_state();
+        record_default_for_breaker(&mut global_state, 0).unwrap();
+        assert!(global_state.breaker_recent_defaults.is_empty());
+    }
+
+    #[test]
+    fn test_circuit_breaker_config_bounds() {
+        let configure = |window_secs, max_defaults| GovernanceAction::ConfigureCircuitBreaker {
+            window_secs,
+            max_defaults,
+        };
+        assert!(validate_governance_action(&configure(3600, MAX_BREAKER_DEFAULTS as u32)).is_ok());
+        assert!(
+            validate_governance_action(&configure(3600, MAX_BREAKER_DEFAULTS as u32 + 1)).is_err()
+        );
+        assert!(validate_governance_action(&configure(-1, 1)).is_err());
+
+        // Reconfiguring forgets earlier defaults
+        let mut global_state = test_global_state();
+        global_state.breaker_recent_defaults = vec![1, 2, 3];
+        apply_governance_action(&mut global_state, &configure(3600, 5), 0).unwrap();
+        assert!(global_state.breaker_recent_defaults.is_empty());
+        assert_eq!(global_state.breaker_max_defaults, 5);
+    }
 }
@@ -0,0 +1,78 @@
+use anchor_lang::prelude::*;
+
+declare_id!("Bb5jumrdv8R4Fp3eW2JemfH3qJ4sguJ3uRtq6n4VuPGB");
+
+pub const PRICE_ACCOUNT_SIZE: usize = 240;
+
+// Pyth v2 price account layout
+const MAGIC: u32 = 0xa1b2c3d4;
+const VERSION: u32 = 2;
+const ACCOUNT_TYPE_PRICE: u32 = 3;
+const STATUS_TRADING: u32 = 1;
+
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const SIZE_OFFSET: usize = 12;
+const EXPO_OFFSET: usize = 20;
+const TIMESTAMP_OFFSET: usize = 96;
+const AGG_PRICE_OFFSET: usize = 208;
+const AGG_CONF_OFFSET: usize = 216;
+const AGG_STATUS_OFFSET: usize = 224;
+const AGG_PUB_SLOT_OFFSET: usize = 232;
+
+/// PLN Protocol Mock Oracle
+/// Writes price accounts in the Pyth v2 layout that `credit_market` reads, so loan
+/// valuation can be exercised on localnet. Anyone can set any price: never deploy
+/// this outside of tests.
+///
+/// Clients create the price account themselves (at least `PRICE_ACCOUNT_SIZE` bytes,
+/// owned by this program) and then call `set_price`.
+#[program]
+pub mod mock_oracle {
+    use super::*;
+
+    /// Write a trading aggregate price of `price * 10^expo` +/- `conf`, published at `publish_time`
+    pub fn set_price(
+        ctx: Context<SetPrice>,
+        price: i64,
+        conf: u64,
+        expo: i32,
+        publish_time: i64,
+    ) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        let mut data = ctx.accounts.price_feed.try_borrow_mut_data()?;
+        require!(data.len() >= PRICE_ACCOUNT_SIZE, MockOracleError::AccountTooSmall);
+
+        write(&mut data, MAGIC_OFFSET, &MAGIC.to_le_bytes());
+        write(&mut data, VERSION_OFFSET, &VERSION.to_le_bytes());
+        write(&mut data, ACCOUNT_TYPE_OFFSET, &ACCOUNT_TYPE_PRICE.to_le_bytes());
+        write(&mut data, SIZE_OFFSET, &(PRICE_ACCOUNT_SIZE as u32).to_le_bytes());
+        write(&mut data, EXPO_OFFSET, &expo.to_le_bytes());
+        write(&mut data, TIMESTAMP_OFFSET, &publish_time.to_le_bytes());
+        write(&mut data, AGG_PRICE_OFFSET, &price.to_le_bytes());
+        write(&mut data, AGG_CONF_OFFSET, &conf.to_le_bytes());
+        write(&mut data, AGG_STATUS_OFFSET, &STATUS_TRADING.to_le_bytes());
+        write(&mut data, AGG_PUB_SLOT_OFFSET, &slot.to_le_bytes());
+
+        msg!("Price set: {} (conf {}, expo {})", price, conf, expo);
+        Ok(())
+    }
+}
+
+fn write(data: &mut [u8], offset: usize, bytes: &[u8]) {
+    data[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+#[derive(Accounts)]
+pub struct SetPrice<'info> {
+    /// CHECK: Raw Pyth-layout price account owned by this program
+    #[account(mut, owner = crate::ID)]
+    pub price_feed: UncheckedAccount<'info>,
+}
+
+#[error_code]
+pub enum MockOracleError {
+    #[msg("Price account is too small")]
+    AccountTooSmall,
+}
@@ -1,49 +1,479 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::{transfer_hook::TransferHookAccount, BaseStateWithExtensions, StateWithExtensions},
+    state::Account as Token2022Account,
+};
 use anchor_spl::token_interface::{Mint, TokenAccount};
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList};
+use spl_transfer_hook_interface::instruction::{ExecuteInstruction, TransferHookInstruction};
 
 declare_id!("7h528dSk5NWWsfBBXA51EdYFZ9XHGsAVEd56smWjKBgg"); // Will be replaced on deploy
 
+pub const MAX_LABEL_LEN: usize = 32;
+pub const MAX_POLICY_WINDOW_SECS: i64 = 30 * 24 * 60 * 60; // 30 days
+pub const DEFAULT_TIMELOCK_DELAY_SECS: i64 = 2 * 24 * 60 * 60; // 48 hours
+pub const MAX_TIMELOCK_DELAY_SECS: i64 = 30 * 24 * 60 * 60; // 30 days
+
 #[program]
 pub mod transfer_hook {
     use super::*;
 
-    pub fn transfer_hook(ctx: Context<TransferHookCtx>) -> Result<()> {
-        let config = &ctx.accounts.whitelist_config;
-        let destination_owner = ctx.accounts.destination_account.owner;
+    /// Allow the transfer if the destination token account is explicitly approved, or if
+    /// its authority is a registered PDA of a program that is still whitelisted.
+    /// Then enforce the source owner's spend policy, falling back to the mint-wide one.
+    /// Sources owned by a registered program PDA, e.g. credit_market loan vaults, are
+    /// exempt from the mint-wide policy and only need velocity state for an owner policy.
+    pub fn transfer_hook(ctx: Context<TransferHookCtx>, amount: u64) -> Result<()> {
+        // Only Token-2022 sets `transferring` for the duration of the hook CPI, so a direct
+        // call can't be used to fill another account's velocity window
+        check_transferring(&ctx.accounts.source_account.to_account_info())?;
+
+        let now = Clock::get()?.unix_timestamp;
+        check_destination(ctx.accounts, now)?;
+
+        let policy = match load_registry_entry::<SpendPolicy>(&ctx.accounts.owner_policy)? {
+            Some(policy) => Some(policy),
+            None if is_program_authority(ctx.accounts, now)? => None,
+            None => load_registry_entry::<SpendPolicy>(&ctx.accounts.mint_policy)?,
+        };
+        if let Some(policy) = policy {
+            let velocity_info = ctx.accounts.velocity_state.to_account_info();
+            let mut velocity = load_registry_entry::<VelocityState>(&velocity_info)?
+                .ok_or(TransferHookError::VelocityStateMissing)?;
+            apply_spend_policy(&policy, &mut velocity, amount, now)?;
+            velocity.try_serialize(&mut &mut velocity_info.try_borrow_mut_data()?[..])?;
+        }
+        Ok(())
+    }
+
+    /// Set spend limits for transfers of `mint`. `owner` of `Pubkey::default()` sets the
+    /// mint-wide policy, any other owner overrides it for that owner's token accounts.
+    pub fn set_spend_policy(
+        ctx: Context<SetSpendPolicy>,
+        owner: Pubkey,
+        limits: SpendLimits,
+    ) -> Result<()> {
+        require!(
+            limits.window_secs >= 0 && limits.window_secs <= MAX_POLICY_WINDOW_SECS,
+            TransferHookError::InvalidSpendPolicy
+        );
+
+        let policy = &mut ctx.accounts.spend_policy;
+        policy.mint = ctx.accounts.mint.key();
+        policy.owner = owner;
+        policy.limits = limits;
+        policy.updated_at = Clock::get()?.unix_timestamp;
+        policy.bump = ctx.bumps.spend_policy;
+        Ok(())
+    }
+
+    /// Remove a spend policy.
+    pub fn remove_spend_policy(_ctx: Context<RemoveSpendPolicy>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Create the velocity counters for a source token account. Permissionless, and
+    /// required before the account can send a mint that has a spend policy, unless the
+    /// account is owned by a registered program PDA and only the mint-wide policy applies.
+    pub fn initialize_velocity_state(ctx: Context<InitializeVelocityState>) -> Result<()> {
+        let velocity = &mut ctx.accounts.velocity_state;
+        velocity.source = ctx.accounts.source_account.key();
+        velocity.window_start = 0;
+        velocity.window_amount = 0;
+        velocity.window_transfers = 0;
+        velocity.prev_window_amount = 0;
+        velocity.prev_window_transfers = 0;
+        velocity.bump = ctx.bumps.velocity_state;
+        Ok(())
+    }
 
-        // Check if the destination account owner is a whitelisted program
+    /// Whitelist a program. The entry only takes effect once the timelock delay has passed,
+    /// so lenders can react before a new destination program goes live.
+    /// `expires_at` of 0 means the entry never expires.
+    pub fn add_whitelisted_program(
+        ctx: Context<AddWhitelistedProgram>,
+        program_id: Pubkey,
+        label: String,
+        expires_at: i64,
+    ) -> Result<()> {
+        require!(label.len() <= MAX_LABEL_LEN, TransferHookError::LabelTooLong);
+        let now = Clock::get()?.unix_timestamp;
         require!(
-            config.whitelisted_programs.contains(&destination_owner),
-            TransferHookError::DestinationNotWhitelisted
+            expires_at == 0 || expires_at > now,
+            TransferHookError::WhitelistEntryExpired
         );
+
+        let entry = &mut ctx.accounts.whitelist_entry;
+        entry.program_id = program_id;
+        entry.label = label;
+        entry.added_by = ctx.accounts.authority.key();
+        entry.added_at = now;
+        entry.active_from = now
+            .checked_add(ctx.accounts.whitelist_config.timelock_delay_secs)
+            .ok_or(TransferHookError::MathOverflow)?;
+        entry.expires_at = expires_at;
+        entry.bump = ctx.bumps.whitelist_entry;
+        Ok(())
+    }
+
+    /// Remove a whitelisted program immediately. Bumps the whitelist epoch, which invalidates
+    /// every registered PDA authority until it is refreshed against a remaining entry.
+    pub fn remove_whitelisted_program(ctx: Context<RemoveWhitelistedProgram>) -> Result<()> {
+        let config = &mut ctx.accounts.whitelist_config;
+        config.whitelist_epoch = config
+            .whitelist_epoch
+            .checked_add(1)
+            .ok_or(TransferHookError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Register a token account authority as a PDA of a whitelisted program. Permissionless:
+    /// `seeds` (including the bump seed) must derive `authority` under `program_id`.
+    pub fn approve_destination_authority(
+        ctx: Context<ApproveDestinationAuthority>,
+        program_id: Pubkey,
+        seeds: Vec<Vec<u8>>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        check_entry_active(&ctx.accounts.whitelist_entry, now)?;
+        let seed_slices: Vec<&[u8]> = seeds.iter().map(|seed| seed.as_slice()).collect();
+        let derived = Pubkey::create_program_address(&seed_slices, &program_id)
+            .map_err(|_| TransferHookError::InvalidDestinationAuthority)?;
+        require_keys_eq!(
+            derived,
+            ctx.accounts.authority.key(),
+            TransferHookError::InvalidDestinationAuthority
+        );
+
+        let entry = &mut ctx.accounts.approved_authority;
+        entry.authority = derived;
+        entry.program_id = program_id;
+        entry.approved_at = now;
+        entry.whitelist_epoch = ctx.accounts.whitelist_config.whitelist_epoch;
+        entry.expires_at = ctx.accounts.whitelist_entry.expires_at;
+        entry.bump = ctx.bumps.approved_authority;
+        Ok(())
+    }
+
+    /// Re-validate a registered PDA authority against its program's whitelist entry after a
+    /// removal bumped the epoch. Permissionless.
+    pub fn refresh_destination_authority(ctx: Context<RefreshDestinationAuthority>) -> Result<()> {
+        check_entry_active(&ctx.accounts.whitelist_entry, Clock::get()?.unix_timestamp)?;
+
+        let entry = &mut ctx.accounts.approved_authority;
+        entry.whitelist_epoch = ctx.accounts.whitelist_config.whitelist_epoch;
+        entry.expires_at = ctx.accounts.whitelist_entry.expires_at;
+        Ok(())
+    }
+
+    /// Remove a registered PDA authority.
+    pub fn revoke_destination_authority(_ctx: Context<RevokeDestinationAuthority>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Approve a specific destination token account, e.g. a self-owned program vault.
+    /// Like a whitelist entry, the approval only takes effect once the timelock delay has passed.
+    pub fn approve_destination(ctx: Context<ApproveDestination>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let entry = &mut ctx.accounts.approved_destination;
+        entry.destination = ctx.accounts.destination.key();
+        entry.approved_at = now;
+        entry.active_from = now
+            .checked_add(ctx.accounts.whitelist_config.timelock_delay_secs)
+            .ok_or(TransferHookError::MathOverflow)?;
+        entry.bump = ctx.bumps.approved_destination;
+        Ok(())
+    }
+
+    /// Remove an approved destination token account.
+    pub fn revoke_destination(_ctx: Context<RevokeDestination>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Write the extra account metas Token-2022 resolves for each transfer of `mint`.
+    /// Must be signed by the mint authority, as in the transfer-hook interface.
+    pub fn initialize_extra_account_meta_list(
+        ctx: Context<InitializeExtraAccountMetaList>,
+    ) -> Result<()> {
+        let extra_account_metas = InitializeExtraAccountMetaList::extra_account_metas()?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?,
+            &extra_account_metas,
+        )?;
         Ok(())
     }
 
-    pub fn initialize_whitelist(
-        ctx: Context<InitializeWhitelist>,
-        programs: Vec<Pubkey>,
+    /// Rewrite the extra account metas for `mint` after `extra_account_metas` changes,
+    /// resizing the list account to fit. Must be signed by the mint authority.
+    pub fn update_extra_account_meta_list(
+        ctx: Context<UpdateExtraAccountMetaList>,
     ) -> Result<()> {
+        let extra_account_metas = InitializeExtraAccountMetaList::extra_account_metas()?;
+        let list = ctx.accounts.extra_account_meta_list.to_account_info();
+        let new_len = ExtraAccountMetaList::size_of(extra_account_metas.len())?;
+        let rent_due = Rent::get()?.minimum_balance(new_len).saturating_sub(list.lamports());
+        if rent_due > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: list.clone(),
+                    },
+                ),
+                rent_due,
+            )?;
+        }
+        list.realloc(new_len, false)?;
+        ExtraAccountMetaList::update::<ExecuteInstruction>(
+            &mut ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?,
+            &extra_account_metas,
+        )?;
+        Ok(())
+    }
+
+    /// Route the transfer-hook interface `Execute` instruction, which uses its own
+    /// discriminator rather than Anchor's, to `transfer_hook`.
+    pub fn fallback<'info>(
+        program_id: &Pubkey,
+        accounts: &'info [AccountInfo<'info>],
+        data: &[u8],
+    ) -> Result<()> {
+        match TransferHookInstruction::unpack(data)? {
+            TransferHookInstruction::Execute { amount } => {
+                __private::__global::transfer_hook(program_id, accounts, &amount.to_le_bytes())
+            }
+            _ => Err(ProgramError::InvalidInstructionData.into()),
+        }
+    }
+
+    pub fn initialize_whitelist(ctx: Context<InitializeWhitelist>) -> Result<()> {
         let config = &mut ctx.accounts.whitelist_config;
-        config.whitelisted_programs = programs;
+        config.whitelist_epoch = 0;
         config.authority = ctx.accounts.authority.key();
+        config.pending_authority = Pubkey::default();
+        config.timelock_delay_secs = DEFAULT_TIMELOCK_DELAY_SECS;
+        config.next_action_id = 1;
         config.bump = ctx.bumps.whitelist_config;
         Ok(())
     }
 
-    pub fn update_whitelist(
-        ctx: Context<UpdateWhitelist>,
-        programs: Vec<Pubkey>,
-    ) -> Result<()> {
+    /// Propose a new authority. Proposing `Pubkey::default()` cancels a pending handover.
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_authority: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.whitelist_config;
+        config.pending_authority = new_authority;
+        Ok(())
+    }
+
+    /// Complete an authority handover, signed by the proposed authority.
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
         let config = &mut ctx.accounts.whitelist_config;
         require!(
-            config.authority == ctx.accounts.authority.key(),
+            config.pending_authority == ctx.accounts.new_authority.key(),
             TransferHookError::Unauthorized
         );
-        config.whitelisted_programs = programs;
+        config.authority = config.pending_authority;
+        config.pending_authority = Pubkey::default();
+        Ok(())
+    }
+
+    /// Queue a timelock change. Anyone can execute it after the delay.
+    pub fn queue_action(ctx: Context<QueueAction>, action: HookAction) -> Result<()> {
+        validate_hook_action(&action)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.whitelist_config;
+        let action_id = config.next_action_id;
+        config.next_action_id = action_id
+            .checked_add(1)
+            .ok_or(TransferHookError::MathOverflow)?;
+
+        let queued = &mut ctx.accounts.queued_action;
+        queued.id = action_id;
+        queued.proposer = ctx.accounts.authority.key();
+        queued.action = action;
+        queued.eta = now
+            .checked_add(config.timelock_delay_secs)
+            .ok_or(TransferHookError::MathOverflow)?;
+        queued.bump = ctx.bumps.queued_action;
+        Ok(())
+    }
+
+    pub fn execute_action(ctx: Context<ExecuteAction>) -> Result<()> {
+        let queued = &ctx.accounts.queued_action;
+        require!(
+            Clock::get()?.unix_timestamp >= queued.eta,
+            TransferHookError::TimelockNotElapsed
+        );
+        apply_hook_action(&mut ctx.accounts.whitelist_config, &queued.action)
+    }
+
+    pub fn cancel_action(_ctx: Context<CancelAction>) -> Result<()> {
         Ok(())
     }
 }
 
+/// Deserialize an optional registry PDA: `None` if it was never created or has been closed
+fn load_registry_entry<T: AccountSerialize + AccountDeserialize + Owner + Clone>(
+    info: &AccountInfo,
+) -> Result<Option<T>> {
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(T::try_deserialize(&mut &info.try_borrow_data()?[..])?))
+}
+
+fn check_transferring(source: &AccountInfo) -> Result<()> {
+    let data = source.try_borrow_data()?;
+    let account = StateWithExtensions::<Token2022Account>::unpack(&data)?;
+    let hook_account = account.get_extension::<TransferHookAccount>()?;
+    require!(
+        bool::from(hook_account.transferring),
+        TransferHookError::NotTransferring
+    );
+    Ok(())
+}
+
+/// Whether the source's authority is a live registration of a whitelisted program's PDA
+fn is_program_authority(accounts: &TransferHookCtx, now: i64) -> Result<bool> {
+    Ok(load_registry_entry::<ApprovedAuthority>(&accounts.source_authority)?
+        .is_some_and(|entry| {
+            entry.whitelist_epoch == accounts.whitelist_config.whitelist_epoch
+                && !is_expired(entry.expires_at, now)
+        }))
+}
+
+fn check_destination(accounts: &TransferHookCtx, now: i64) -> Result<()> {
+    // An approval still in its timelock falls through to the authority check
+    if load_registry_entry::<ApprovedDestination>(&accounts.approved_destination)?
+        .is_some_and(|entry| now >= entry.active_from)
+    {
+        return Ok(());
+    }
+
+    let approved_authority = load_registry_entry::<ApprovedAuthority>(&accounts.approved_authority)?
+        .ok_or(TransferHookError::DestinationNotApproved)?;
+    // Any program removal bumps the epoch, so registrations must be refreshed
+    // against a whitelist entry that still exists
+    require!(
+        approved_authority.whitelist_epoch == accounts.whitelist_config.whitelist_epoch,
+        TransferHookError::DestinationAuthorityStale
+    );
+    require!(
+        !is_expired(approved_authority.expires_at, now),
+        TransferHookError::WhitelistEntryExpired
+    );
+    Ok(())
+}
+
+/// Record a transfer against the source's rolling window, failing if it breaks a limit.
+/// A zero limit is unlimited; a zero window only enforces `max_per_transfer`.
+/// The window slides: the previous bucket still counts in proportion to how much of it
+/// overlaps the last `window_secs`, so a burst split across a bucket boundary is caught.
+fn apply_spend_policy(
+    policy: &SpendPolicy,
+    velocity: &mut VelocityState,
+    amount: u64,
+    now: i64,
+) -> Result<()> {
+    let limits = &policy.limits;
+    require!(
+        limits.max_per_transfer == 0 || amount <= limits.max_per_transfer,
+        TransferHookError::TransferAmountLimitExceeded
+    );
+    if limits.window_secs == 0 {
+        return Ok(());
+    }
+
+    let window = limits.window_secs;
+    let elapsed = now.saturating_sub(velocity.window_start);
+    if elapsed >= window.saturating_mul(2) {
+        velocity.window_start = now;
+        velocity.prev_window_amount = 0;
+        velocity.prev_window_transfers = 0;
+        velocity.window_amount = 0;
+        velocity.window_transfers = 0;
+    } else if elapsed >= window {
+        velocity.window_start = velocity
+            .window_start
+            .checked_add(window)
+            .ok_or(TransferHookError::MathOverflow)?;
+        velocity.prev_window_amount = velocity.window_amount;
+        velocity.prev_window_transfers = velocity.window_transfers;
+        velocity.window_amount = 0;
+        velocity.window_transfers = 0;
+    }
+
+    velocity.window_amount = velocity
+        .window_amount
+        .checked_add(amount)
+        .ok_or(TransferHookError::MathOverflow)?;
+    velocity.window_transfers = velocity
+        .window_transfers
+        .checked_add(1)
+        .ok_or(TransferHookError::MathOverflow)?;
+
+    // Share of the previous bucket still inside the window, rounded up
+    let overlap = window
+        .saturating_sub(now.saturating_sub(velocity.window_start))
+        .clamp(0, window) as u128;
+    let weighted = |prev: u64, current: u64| -> Result<u128> {
+        (prev as u128)
+            .checked_mul(overlap)
+            .map(|v| v.div_ceil(window as u128))
+            .and_then(|v| v.checked_add(current as u128))
+            .ok_or(error!(TransferHookError::MathOverflow))
+    };
+    require!(
+        limits.max_per_window == 0
+            || weighted(velocity.prev_window_amount, velocity.window_amount)?
+                <= limits.max_per_window as u128,
+        TransferHookError::WindowAmountLimitExceeded
+    );
+    require!(
+        limits.max_transfers_per_window == 0
+            || weighted(
+                velocity.prev_window_transfers as u64,
+                velocity.window_transfers as u64,
+            )? <= limits.max_transfers_per_window as u128,
+        TransferHookError::WindowTransferLimitExceeded
+    );
+    Ok(())
+}
+
+fn is_expired(expires_at: i64, now: i64) -> bool {
+    expires_at != 0 && now >= expires_at
+}
+
+fn check_entry_active(entry: &WhitelistEntry, now: i64) -> Result<()> {
+    require!(now >= entry.active_from, TransferHookError::WhitelistEntryNotActive);
+    require!(!is_expired(entry.expires_at, now), TransferHookError::WhitelistEntryExpired);
+    Ok(())
+}
+
+fn validate_hook_action(action: &HookAction) -> Result<()> {
+    match action {
+        HookAction::SetTimelockDelay { delay_secs } => {
+            require!(
+                (0..=MAX_TIMELOCK_DELAY_SECS).contains(delay_secs),
+                TransferHookError::InvalidTimelockDelay
+            );
+        }
+    }
+    Ok(())
+}
+
+fn apply_hook_action(config: &mut WhitelistConfig, action: &HookAction) -> Result<()> {
+    validate_hook_action(action)?;
+    match action {
+        HookAction::SetTimelockDelay { delay_secs } => {
+            config.timelock_delay_secs = *delay_secs;
+        }
+    }
+    Ok(())
+}
+
 #[derive(Accounts)]
 pub struct TransferHookCtx<'info> {
     /// CHECK: Source token account
@@ -54,12 +484,378 @@ pub struct TransferHookCtx<'info> {
     /// CHECK: Owner of source
     pub owner: UncheckedAccount<'info>,
     /// CHECK: Extra account metas PDA
+    #[account(seeds = [b"extra-account-metas", mint.key().as_ref()], bump)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    /// CHECK: Registry entry for the destination's authority, may not exist
+    #[account(seeds = [b"approved_authority", destination_account.owner.as_ref()], bump)]
+    pub approved_authority: UncheckedAccount<'info>,
+    /// CHECK: Registry entry for the destination token account, may not exist
+    #[account(seeds = [b"approved_destination", destination_account.key().as_ref()], bump)]
+    pub approved_destination: UncheckedAccount<'info>,
+    /// CHECK: Mint-wide spend policy, may not exist
+    #[account(seeds = [b"policy", mint.key().as_ref(), Pubkey::default().as_ref()], bump)]
+    pub mint_policy: UncheckedAccount<'info>,
+    /// CHECK: Spend policy for the source account's owner, may not exist
+    #[account(seeds = [b"policy", mint.key().as_ref(), source_account.owner.as_ref()], bump)]
+    pub owner_policy: UncheckedAccount<'info>,
+    /// CHECK: Velocity counters for the source account, required if a policy applies
+    #[account(mut, seeds = [b"velocity", source_account.key().as_ref()], bump)]
+    pub velocity_state: UncheckedAccount<'info>,
+    /// CHECK: Registry entry for the source's authority, may not exist
+    #[account(seeds = [b"approved_authority", source_account.owner.as_ref()], bump)]
+    pub source_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeExtraAccountMetaList<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: TLV account-meta list, written by `initialize_extra_account_meta_list`
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(
+            InitializeExtraAccountMetaList::extra_account_metas()?.len()
+        )?,
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump,
+    )]
     pub extra_account_meta_list: UncheckedAccount<'info>,
+    #[account(
+        constraint = mint.mint_authority == Some(mint_authority.key()).into()
+            @ TransferHookError::Unauthorized,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub mint_authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateExtraAccountMetaList<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: TLV account-meta list, rewritten by `update_extra_account_meta_list`
+    #[account(
+        mut,
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump,
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    #[account(
+        constraint = mint.mint_authority == Some(mint_authority.key()).into()
+            @ TransferHookError::Unauthorized,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub mint_authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeExtraAccountMetaList<'info> {
+    /// Accounts appended to `Execute` after the standard five: the `[b"whitelist"]` config,
+    /// the registry entry for the destination's authority (token account bytes 32..64),
+    /// the registry entry for the destination token account itself (index 2), the mint-wide
+    /// and source-owner spend policies, the source's writable velocity counters, and the
+    /// registry entry for the source's authority (token account bytes 32..64).
+    pub fn extra_account_metas() -> Result<Vec<ExtraAccountMeta>> {
+        Ok(vec![
+            ExtraAccountMeta::new_with_seeds(
+                &[Seed::Literal {
+                    bytes: b"whitelist".to_vec(),
+                }],
+                false, // is_signer
+                false, // is_writable
+            )?,
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::Literal {
+                        bytes: b"approved_authority".to_vec(),
+                    },
+                    Seed::AccountData {
+                        account_index: 2,
+                        data_index: 32,
+                        length: 32,
+                    },
+                ],
+                false,
+                false,
+            )?,
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::Literal {
+                        bytes: b"approved_destination".to_vec(),
+                    },
+                    Seed::AccountKey { index: 2 },
+                ],
+                false,
+                false,
+            )?,
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::Literal {
+                        bytes: b"policy".to_vec(),
+                    },
+                    Seed::AccountKey { index: 1 },
+                    Seed::Literal {
+                        bytes: Pubkey::default().to_bytes().to_vec(),
+                    },
+                ],
+                false,
+                false,
+            )?,
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::Literal {
+                        bytes: b"policy".to_vec(),
+                    },
+                    Seed::AccountKey { index: 1 },
+                    Seed::AccountData {
+                        account_index: 0,
+                        data_index: 32,
+                        length: 32,
+                    },
+                ],
+                false,
+                false,
+            )?,
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::Literal {
+                        bytes: b"velocity".to_vec(),
+                    },
+                    Seed::AccountKey { index: 0 },
+                ],
+                false,
+                true, // is_writable
+            )?,
+            ExtraAccountMeta::new_with_seeds(
+                &[
+                    Seed::Literal {
+                        bytes: b"approved_authority".to_vec(),
+                    },
+                    Seed::AccountData {
+                        account_index: 0,
+                        data_index: 32,
+                        length: 32,
+                    },
+                ],
+                false,
+                false,
+            )?,
+        ])
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct SetSpendPolicy<'info> {
+    #[account(
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + SpendPolicy::SIZE,
+        seeds = [b"policy", mint.key().as_ref(), owner.as_ref()],
+        bump,
+    )]
+    pub spend_policy: Account<'info, SpendPolicy>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveSpendPolicy<'info> {
+    #[account(
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"policy", spend_policy.mint.as_ref(), spend_policy.owner.as_ref()],
+        bump = spend_policy.bump,
+    )]
+    pub spend_policy: Account<'info, SpendPolicy>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVelocityState<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub source_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + VelocityState::SIZE,
+        seeds = [b"velocity", source_account.key().as_ref()],
+        bump,
+    )]
+    pub velocity_state: Account<'info, VelocityState>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(program_id: Pubkey)]
+pub struct AddWhitelistedProgram<'info> {
+    #[account(
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + WhitelistEntry::SIZE,
+        seeds = [b"wl", program_id.as_ref()],
+        bump,
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveWhitelistedProgram<'info> {
+    #[account(
+        mut,
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"wl", whitelist_entry.program_id.as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(program_id: Pubkey)]
+pub struct ApproveDestinationAuthority<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Proven to be a PDA of `program_id` in the handler
+    pub authority: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ApprovedAuthority::SIZE,
+        seeds = [b"approved_authority", authority.key().as_ref()],
+        bump,
+    )]
+    pub approved_authority: Account<'info, ApprovedAuthority>,
+    #[account(
+        seeds = [b"wl", program_id.as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    #[account(
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshDestinationAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"approved_authority", approved_authority.authority.as_ref()],
+        bump = approved_authority.bump,
+    )]
+    pub approved_authority: Account<'info, ApprovedAuthority>,
+    #[account(
+        seeds = [b"wl", approved_authority.program_id.as_ref()],
+        bump = whitelist_entry.bump,
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,
+    #[account(
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeDestinationAuthority<'info> {
     #[account(
         seeds = [b"whitelist"],
         bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
     )]
     pub whitelist_config: Account<'info, WhitelistConfig>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"approved_authority", approved_authority.authority.as_ref()],
+        bump = approved_authority.bump,
+    )]
+    pub approved_authority: Account<'info, ApprovedAuthority>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveDestination<'info> {
+    #[account(
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ApprovedDestination::SIZE,
+        seeds = [b"approved_destination", destination.key().as_ref()],
+        bump,
+    )]
+    pub approved_destination: Account<'info, ApprovedDestination>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeDestination<'info> {
+    #[account(
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"approved_destination", approved_destination.destination.as_ref()],
+        bump = approved_destination.bump,
+    )]
+    pub approved_destination: Account<'info, ApprovedDestination>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
@@ -67,7 +863,7 @@ pub struct InitializeWhitelist<'info> {
     #[account(
         init,
         payer = authority,
-        space = 8 + 4 + (32 * 10) + 32 + 1, // discriminator + vec len + 10 pubkeys max + authority + bump
+        space = 8 + WhitelistConfig::SIZE,
         seeds = [b"whitelist"],
         bump,
     )]
@@ -78,27 +874,360 @@ pub struct InitializeWhitelist<'info> {
 }
 
 #[derive(Accounts)]
-pub struct UpdateWhitelist<'info> {
+pub struct ProposeAdmin<'info> {
     #[account(
         mut,
         seeds = [b"whitelist"],
         bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
     )]
     pub whitelist_config: Account<'info, WhitelistConfig>,
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct QueueAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + QueuedAction::SIZE,
+        seeds = [b"queued_action", whitelist_config.next_action_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"queued_action", queued_action.id.to_le_bytes().as_ref()],
+        bump = queued_action.bump,
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+    /// CHECK: Receives the queued action's rent, must match its proposer
+    #[account(mut, address = queued_action.proposer)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAction<'info> {
+    #[account(
+        seeds = [b"whitelist"],
+        bump = whitelist_config.bump,
+        has_one = authority @ TransferHookError::Unauthorized,
+    )]
+    pub whitelist_config: Account<'info, WhitelistConfig>,
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"queued_action", queued_action.id.to_le_bytes().as_ref()],
+        bump = queued_action.bump,
+    )]
+    pub queued_action: Account<'info, QueuedAction>,
+    /// CHECK: Receives the queued action's rent, must match its proposer
+    #[account(mut, address = queued_action.proposer)]
+    pub proposer: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+}
+
 #[account]
 pub struct WhitelistConfig {
-    pub whitelisted_programs: Vec<Pubkey>,
+    /// Bumped on every program removal, invalidating registered PDA authorities
+    pub whitelist_epoch: u64,
     pub authority: Pubkey,
-    pub pub bump: u8, // Corrected from `pub bump: u8` to `pub bump: u8`.
+    pub pending_authority: Pubkey,
+    pub timelock_delay_secs: i64,
+    pub next_action_id: u64,
+    pub bump: u8,
+}
+
+impl WhitelistConfig {
+    // epoch + authority + pending authority + delay + next id + bump
+    pub const SIZE: usize = 8 + 32 + 32 + 8 + 8 + 1;
+}
+
+/// A whitelisted program, one PDA per program at `[b"wl", program_id]`
+#[account]
+pub struct WhitelistEntry {
+    pub program_id: Pubkey,
+    pub label: String,
+    pub added_by: Pubkey,
+    pub added_at: i64,
+    /// `added_at` plus the timelock delay at the time it was added
+    pub active_from: i64,
+    /// 0 = never expires
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+impl WhitelistEntry {
+    pub const SIZE: usize = 32 + 4 + MAX_LABEL_LEN + 32 + 8 + 8 + 8 + 1;
+}
+
+/// A token account authority proven to be a PDA of a whitelisted program
+#[account]
+pub struct ApprovedAuthority {
+    pub authority: Pubkey,
+    pub program_id: Pubkey,
+    pub approved_at: i64,
+    /// `WhitelistConfig::whitelist_epoch` when last validated
+    pub whitelist_epoch: u64,
+    /// Copied from the program's whitelist entry, 0 = never expires
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+impl ApprovedAuthority {
+    pub const SIZE: usize = 32 + 32 + 8 + 8 + 8 + 1;
+}
+
+/// A destination token account approved directly by the whitelist authority
+#[account]
+pub struct ApprovedDestination {
+    pub destination: Pubkey,
+    pub approved_at: i64,
+    /// `approved_at` plus the timelock delay at the time it was approved
+    pub active_from: i64,
+    pub bump: u8,
+}
+
+impl ApprovedDestination {
+    pub const SIZE: usize = 32 + 8 + 8 + 1;
+}
+
+/// Spend limits for a mint, either mint-wide or for one source owner. 0 = unlimited.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SpendLimits {
+    pub max_per_transfer: u64,
+    pub max_per_window: u64,
+    pub max_transfers_per_window: u32,
+    pub window_secs: i64,
+}
+
+impl SpendLimits {
+    pub const SIZE: usize = 8 + 8 + 4 + 8;
+}
+
+#[account]
+pub struct SpendPolicy {
+    pub mint: Pubkey,
+    /// `Pubkey::default()` for the mint-wide policy
+    pub owner: Pubkey,
+    pub limits: SpendLimits,
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+impl SpendPolicy {
+    pub const SIZE: usize = 32 + 32 + SpendLimits::SIZE + 8 + 1;
+}
+
+/// Rolling-window counters for one source token account, updated by every transfer.
+/// Kept as the current bucket of `window_secs` from `window_start` plus the one before it.
+#[account]
+pub struct VelocityState {
+    pub source: Pubkey,
+    pub window_start: i64,
+    pub window_amount: u64,
+    pub window_transfers: u32,
+    pub prev_window_amount: u64,
+    pub prev_window_transfers: u32,
+    pub bump: u8,
+}
+
+impl VelocityState {
+    pub const SIZE: usize = 32 + 8 + 8 + 4 + 8 + 4 + 1;
+}
+
+#[account]
+pub struct QueuedAction {
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub action: HookAction,
+    pub eta: i64,
+    pub bump: u8,
+}
+
+impl QueuedAction {
+    pub const SIZE: usize = 8 + 32 + HookAction::SIZE + 8 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum HookAction {
+    SetTimelockDelay { delay_secs: i64 },
+}
+
+impl HookAction {
+    // enum tag + delay
+    pub const SIZE: usize = 1 + 8;
 }
 
 #[error_code]
 pub enum TransferHookError {
-    #[msg("Destination program is not whitelisted by PLN protocol.")]
-    DestinationNotWhitelisted,
+    #[msg("Destination is not an approved token account and its authority is not a registered program PDA.")]
+    DestinationNotApproved,
+    #[msg("Seeds do not derive the authority from the given program.")]
+    InvalidDestinationAuthority,
     #[msg("Unauthorized to update whitelist.")]
     Unauthorized,
+    #[msg("Whitelist label is too long.")]
+    LabelTooLong,
+    #[msg("Whitelist entry is not active yet.")]
+    WhitelistEntryNotActive,
+    #[msg("Whitelist entry has expired.")]
+    WhitelistEntryExpired,
+    #[msg("Destination authority must be refreshed after a whitelist removal.")]
+    DestinationAuthorityStale,
+    #[msg("Invalid spend policy.")]
+    InvalidSpendPolicy,
+    #[msg("Source account has no velocity state, call initialize_velocity_state.")]
+    VelocityStateMissing,
+    #[msg("Transfer exceeds the per-transfer limit.")]
+    TransferAmountLimitExceeded,
+    #[msg("Transfer exceeds the amount allowed per window.")]
+    WindowAmountLimitExceeded,
+    #[msg("Transfer exceeds the number of transfers allowed per window.")]
+    WindowTransferLimitExceeded,
+    #[msg("Timelock has not elapsed for this action.")]
+    TimelockNotElapsed,
+    #[msg("Invalid timelock delay.")]
+    InvalidTimelockDelay,
+    #[msg("Hook was not invoked by Token-2022 during a transfer.")]
+    NotTransferring,
+    #[msg("Math overflow.")]
+    MathOverflow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(
+        max_per_transfer: u64,
+        max_per_window: u64,
+        max_transfers: u32,
+        window_secs: i64,
+    ) -> SpendPolicy {
+        SpendPolicy {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::default(),
+            limits: SpendLimits {
+                max_per_transfer,
+                max_per_window,
+                max_transfers_per_window: max_transfers,
+                window_secs,
+            },
+            updated_at: 0,
+            bump: 255,
+        }
+    }
+
+    fn velocity() -> VelocityState {
+        VelocityState {
+            source: Pubkey::new_unique(),
+            window_start: 0,
+            window_amount: 0,
+            window_transfers: 0,
+            prev_window_amount: 0,
+            prev_window_transfers: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn test_per_transfer_limit() {
+        let policy = policy(100, 0, 0, 0);
+        let mut velocity = velocity();
+        assert!(apply_spend_policy(&policy, &mut velocity, 100, 1_000).is_ok());
+        assert!(apply_spend_policy(&policy, &mut velocity, 101, 1_000).is_err());
+        // No window, so nothing accumulates
+        for _ in 0..10 {
+            apply_spend_policy(&policy, &mut velocity, 100, 1_000).unwrap();
+        }
+        assert_eq!(velocity.window_amount, 0);
+    }
+
+    #[test]
+    fn test_window_amount_limit() {
+        let policy = policy(0, 1_000, 0, 100);
+        let mut velocity = velocity();
+        apply_spend_policy(&policy, &mut velocity, 600, 1_000).unwrap();
+        apply_spend_policy(&policy, &mut velocity, 400, 1_050).unwrap();
+        assert!(apply_spend_policy(&policy, &mut velocity.clone(), 1, 1_099).is_err());
+        // Two full windows later everything has aged out
+        apply_spend_policy(&policy, &mut velocity, 1_000, 1_200).unwrap();
+        assert_eq!(velocity.window_start, 1_200);
+        assert_eq!(velocity.prev_window_amount, 0);
+    }
+
+    #[test]
+    fn test_window_straddling_burst_is_limited() {
+        let policy = policy(0, 1_000, 0, 100);
+        let mut velocity = velocity();
+        apply_spend_policy(&policy, &mut velocity, 1, 1_000).unwrap();
+        // The rest of the allowance right before the bucket ends...
+        apply_spend_policy(&policy, &mut velocity, 999, 1_099).unwrap();
+        // ...still counts right after it, where a tumbling window would have reset
+        let mut next = velocity.clone();
+        assert!(apply_spend_policy(&policy, &mut next, 1, 1_100).is_err());
+        // It decays linearly over the next window
+        let mut next = velocity.clone();
+        assert!(apply_spend_policy(&policy, &mut next, 501, 1_150).is_err());
+        let mut next = velocity.clone();
+        apply_spend_policy(&policy, &mut next, 500, 1_150).unwrap();
+        let mut next = velocity.clone();
+        apply_spend_policy(&policy, &mut next, 600, 1_160).unwrap();
+        assert_eq!(next.window_start, 1_100);
+        assert_eq!(next.prev_window_amount, 1_000);
+        let mut next = velocity.clone();
+        assert!(apply_spend_policy(&policy, &mut next, 1_000, 1_199).is_err());
+        let mut next = velocity;
+        apply_spend_policy(&policy, &mut next, 1_000, 1_200).unwrap();
+    }
+
+    #[test]
+    fn test_window_transfer_limit() {
+        let policy = policy(0, 0, 4, 100);
+        let mut velocity = velocity();
+        for t in [1_000, 1_090, 1_095, 1_099] {
+            apply_spend_policy(&policy, &mut velocity, 1, t).unwrap();
+        }
+        assert!(apply_spend_policy(&policy, &mut velocity.clone(), 1, 1_099).is_err());
+        // A full bucket still blocks the first transfer of the next one
+        assert!(apply_spend_policy(&policy, &mut velocity.clone(), 1, 1_100).is_err());
+        // Half the previous bucket still counts half way through
+        let mut next = velocity.clone();
+        apply_spend_policy(&policy, &mut next, 1, 1_150).unwrap();
+        apply_spend_policy(&policy, &mut next, 1, 1_150).unwrap();
+        assert!(apply_spend_policy(&policy, &mut next, 1, 1_150).is_err());
+    }
 }